@@ -0,0 +1,1245 @@
+use std::ffi::c_void;
+#[cfg(unix)]
+use std::os::unix::io::RawFd;
+use std::ptr::NonNull;
+
+use gpu_alloc_types::{AllocationFlags, DeviceProperties, MemoryPropertyFlags};
+#[cfg(feature = "smallvec")]
+use smallvec::SmallVec;
+use vulkanalia::prelude::v1_0::*;
+use vulkanalia::vk::ExtPageableDeviceLocalMemoryExtensionDeviceCommands;
+
+use crate::{memory_type_props, VulkanaliaMemoryDevice, VulkanaliaMemoryError};
+
+/// Resource a dedicated allocation is bound to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum DedicatedTarget {
+    /// Memory will be bound only to this buffer.
+    Buffer(vk::Buffer),
+    /// Memory will be bound only to this image.
+    Image(vk::Image),
+}
+
+/// Memory object over-allocated by [`VulkanaliaMemoryDevice::allocate_aligned_memory`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct AlignedMemory {
+    /// Allocated memory object.
+    pub memory: vk::DeviceMemory,
+    /// Actual size of the memory object, including padding.
+    pub allocation_size: u64,
+    /// Requested alignment, at least 1.
+    pub alignment: u64,
+}
+
+impl AlignedMemory {
+    /// Returns offset within the memory object at which the usable region starts,
+    /// when the start of the memory object is at `base_address`.
+    ///
+    /// `base_address` is whatever the alignment is required for, e.g. a device address
+    /// or an address of the memory in the external consumer.
+    /// The returned offset is always less than `alignment`.
+    pub fn offset_for(&self, base_address: u64) -> u64 {
+        let alignment = self.alignment.max(1);
+        match base_address % alignment {
+            0 => 0,
+            misalignment => alignment - misalignment,
+        }
+    }
+}
+
+/// Optional structures chained into `VkMemoryAllocateInfo`.
+#[derive(Clone, Copy, Debug, Default)]
+pub(crate) struct AllocateExtensions<'a> {
+    /// Host memory allocator passed to `vkAllocateMemory`.
+    pub allocation_callbacks: Option<&'a vk::AllocationCallbacks>,
+    /// `VkMemoryAllocateFlagsInfo::deviceMask`, sets `DEVICE_MASK` allocate flag.
+    pub device_mask: Option<u32>,
+    /// `VkMemoryPriorityAllocateInfoEXT::priority`.
+    pub priority: Option<f32>,
+    /// `VkMemoryOpaqueCaptureAddressAllocateInfo::opaqueCaptureAddress`, ignored when zero.
+    pub opaque_capture_address: u64,
+    /// Set `DEVICE_ADDRESS_CAPTURE_REPLAY` allocate flag even without a capture address.
+    pub capture_replay: bool,
+    /// `VkMemoryDedicatedAllocateInfo::buffer` or `VkMemoryDedicatedAllocateInfo::image`.
+    pub dedicated: Option<DedicatedTarget>,
+    /// `VkExportMemoryAllocateInfo::handleTypes`.
+    pub export: Option<vk::ExternalMemoryHandleTypeFlags>,
+    /// `VkImportMemoryFdInfoKHR::handleType` and `VkImportMemoryFdInfoKHR::fd`.
+    #[cfg(unix)]
+    pub import_fd: Option<(vk::ExternalMemoryHandleTypeFlags, RawFd)>,
+    /// `VkImportMemoryHostPointerInfoEXT::handleType` and `VkImportMemoryHostPointerInfoEXT::pHostPointer`.
+    pub import_host_pointer: Option<(vk::ExternalMemoryHandleTypeFlags, NonNull<u8>)>,
+    /// `VkExportMemoryWin32HandleInfoKHR`, its `next` pointer is ignored.
+    #[cfg(feature = "win32")]
+    pub export_win32: Option<vk::ExportMemoryWin32HandleInfoKHR>,
+    /// Caller-built chain linked after all other structures.
+    pub next: Option<NonNull<c_void>>,
+}
+
+/// Collects optional structures chained into `VkMemoryAllocateInfo`
+/// and allocates memory with all of them at once.
+///
+/// Each setter corresponds to one of the specialized `allocate_*` methods of
+/// [`VulkanaliaMemoryDevice`] and has the same requirements on enabled extensions
+/// and features. The structures are built and chained only during [`allocate`](Self::allocate).
+///
+/// Not every combination is valid:
+/// - Importing with [`import_fd`](Self::import_fd) excludes [`export`](Self::export),
+///   [`priority`](Self::priority) is still allowed.
+/// - [`dedicated_buffer`](Self::dedicated_buffer) and [`dedicated_image`](Self::dedicated_image)
+///   replace each other.
+/// - [`opaque_capture_address`](Self::opaque_capture_address) is not allowed
+///   with [`device_mask`](Self::device_mask) selecting several physical devices.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct AllocateInfoBuilder<'a> {
+    ext: AllocateExtensions<'a>,
+}
+
+impl<'a> AllocateInfoBuilder<'a> {
+    /// Creates a builder which chains nothing.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Passes `callbacks` to `vkAllocateMemory`.
+    pub fn allocation_callbacks(mut self, callbacks: &'a vk::AllocationCallbacks) -> Self {
+        self.ext.allocation_callbacks = Some(callbacks);
+        self
+    }
+
+    /// Chains `VkMemoryPriorityAllocateInfoEXT`,
+    /// see [`VulkanaliaMemoryDevice::allocate_memory_with_priority`].
+    pub fn priority(mut self, priority: f32) -> Self {
+        self.ext.priority = Some(priority);
+        self
+    }
+
+    /// Sets `VkMemoryAllocateFlagsInfo::deviceMask`,
+    /// see [`VulkanaliaMemoryDevice::allocate_memory_device_mask`].
+    pub fn device_mask(mut self, device_mask: u32) -> Self {
+        self.ext.device_mask = Some(device_mask);
+        self
+    }
+
+    /// Chains `VkMemoryOpaqueCaptureAddressAllocateInfo`, ignored when zero,
+    /// see [`VulkanaliaMemoryDevice::allocate_memory_with_capture_address`].
+    pub fn opaque_capture_address(mut self, address: u64) -> Self {
+        self.ext.opaque_capture_address = address;
+        self
+    }
+
+    /// Chains `VkMemoryDedicatedAllocateInfo` for the `buffer`,
+    /// see [`VulkanaliaMemoryDevice::allocate_dedicated_memory`].
+    pub fn dedicated_buffer(mut self, buffer: vk::Buffer) -> Self {
+        self.ext.dedicated = Some(DedicatedTarget::Buffer(buffer));
+        self
+    }
+
+    /// Chains `VkMemoryDedicatedAllocateInfo` for the `image`,
+    /// see [`VulkanaliaMemoryDevice::allocate_dedicated_memory`].
+    pub fn dedicated_image(mut self, image: vk::Image) -> Self {
+        self.ext.dedicated = Some(DedicatedTarget::Image(image));
+        self
+    }
+
+    /// Chains `VkExportMemoryAllocateInfo`,
+    /// see [`VulkanaliaMemoryDevice::allocate_exportable_memory`].
+    pub fn export(mut self, handle_types: vk::ExternalMemoryHandleTypeFlags) -> Self {
+        self.ext.export = Some(handle_types);
+        self
+    }
+
+    /// Chains `VkImportMemoryFdInfoKHR`,
+    /// see [`VulkanaliaMemoryDevice::import_memory_fd`].
+    #[cfg(unix)]
+    pub fn import_fd(mut self, handle_type: vk::ExternalMemoryHandleTypeFlags, fd: RawFd) -> Self {
+        self.ext.import_fd = Some((handle_type, fd));
+        self
+    }
+
+    /// Allocates new memory object with the collected structures chained.
+    ///
+    /// # Safety
+    ///
+    /// Same as for [`MemoryDevice::allocate_memory`](gpu_alloc_types::MemoryDevice::allocate_memory),
+    /// along with the requirements of the methods corresponding to each setter used.
+    /// The combination of structures must be valid.
+    pub unsafe fn allocate(
+        &self,
+        device: &VulkanaliaMemoryDevice,
+        size: u64,
+        memory_type: u32,
+        flags: AllocationFlags,
+    ) -> Result<vk::DeviceMemory, VulkanaliaMemoryError> {
+        device.allocate_memory_with(size, memory_type, flags, &self.ext)
+    }
+}
+
+/// Changes priority of the memory object using `vkSetDeviceMemoryPriorityEXT`,
+/// e.g. to let the driver evict memory which is about to be freed or is rarely used.
+///
+/// The value is clamped to `[0.0, 1.0]`, NaN is replaced with the default priority `0.5`.
+///
+/// # Safety
+///
+/// - Extension "VK_EXT_pageable_device_local_memory" and its `pageableDeviceLocalMemory`
+///   feature must be enabled on device creation.
+/// - `memory` must have been allocated from the `device`.
+pub unsafe fn set_memory_priority(device: &Device, memory: vk::DeviceMemory, priority: f32) {
+    device.set_device_memory_priority_ext(memory, clamp_priority(priority));
+}
+
+/// Clamps memory priority to the range accepted by Vulkan.
+fn clamp_priority(priority: f32) -> f32 {
+    if priority.is_nan() {
+        0.5
+    } else {
+        priority.clamp(0.0, 1.0)
+    }
+}
+
+/// Returns structures chained for a dedicated allocation of `target`.
+fn dedicated_extensions(target: DedicatedTarget) -> AllocateExtensions<'static> {
+    AllocateExtensions {
+        dedicated: Some(target),
+        ..Default::default()
+    }
+}
+
+/// Binds new memory object with `bind`, freeing it with `free` if binding fails.
+fn bind_or_free<M: Copy, E>(
+    memory: M,
+    bind: impl FnOnce(M) -> Result<(), E>,
+    free: impl FnOnce(M),
+) -> Result<M, E> {
+    match bind(memory) {
+        Ok(()) => Ok(memory),
+        Err(e) => {
+            free(memory);
+            Err(e)
+        }
+    }
+}
+
+/// Allocates each request with `allocate`, collecting results in the order of `requests`.
+#[cfg(feature = "smallvec")]
+fn allocate_batch<M, E>(
+    requests: &[(u64, u32, AllocationFlags)],
+    mut allocate: impl FnMut(u64, u32, AllocationFlags) -> Result<M, E>,
+) -> SmallVec<[Result<M, E>; 8]> {
+    requests
+        .iter()
+        .map(|&(size, memory_type, flags)| allocate(size, memory_type, flags))
+        .collect()
+}
+
+/// Builds `VkMemoryAllocateInfo` with the structures of `ext` chained into it
+/// and passes it to `f`, the structures are only valid during the call.
+pub(crate) fn with_allocate_info<R>(
+    size: u64,
+    memory_type: u32,
+    flags: AllocationFlags,
+    ext: &AllocateExtensions<'_>,
+    f: impl FnOnce(&vk::MemoryAllocateInfo) -> R,
+) -> R {
+    let mut info = vk::MemoryAllocateInfo::builder()
+        .allocation_size(size)
+        .memory_type_index(memory_type);
+
+    // Structures pushed below are prepended, so the caller's chain stays last
+    if let Some(next) = ext.next {
+        info.next = next.as_ptr();
+    }
+
+    let allocate_flags = memory_allocate_flags(flags, ext);
+    let mut info_flags;
+
+    if !allocate_flags.is_empty() {
+        info_flags = vk::MemoryAllocateFlagsInfo::builder()
+            .flags(allocate_flags)
+            .device_mask(ext.device_mask.unwrap_or(0));
+        info = info.push_next(&mut info_flags);
+    }
+
+    let mut info_capture_address;
+
+    if ext.opaque_capture_address != 0 {
+        info_capture_address = vk::MemoryOpaqueCaptureAddressAllocateInfo::builder()
+            .opaque_capture_address(ext.opaque_capture_address);
+        info = info.push_next(&mut info_capture_address);
+    }
+
+    let mut info_priority;
+
+    if let Some(priority) = ext.priority {
+        info_priority =
+            vk::MemoryPriorityAllocateInfoEXT::builder().priority(clamp_priority(priority));
+        info = info.push_next(&mut info_priority);
+    }
+
+    let mut info_dedicated;
+
+    if let Some(dedicated) = ext.dedicated {
+        info_dedicated = match dedicated {
+            DedicatedTarget::Buffer(buffer) => {
+                vk::MemoryDedicatedAllocateInfo::builder().buffer(buffer)
+            }
+            DedicatedTarget::Image(image) => {
+                vk::MemoryDedicatedAllocateInfo::builder().image(image)
+            }
+        };
+        info = info.push_next(&mut info_dedicated);
+    }
+
+    let mut info_export;
+
+    if let Some(handle_types) = ext.export {
+        info_export = vk::ExportMemoryAllocateInfo::builder().handle_types(handle_types);
+        info = info.push_next(&mut info_export);
+    }
+
+    #[cfg(unix)]
+    let mut info_import_fd;
+
+    #[cfg(unix)]
+    if let Some((handle_type, fd)) = ext.import_fd {
+        info_import_fd = vk::ImportMemoryFdInfoKHR::builder()
+            .handle_type(handle_type)
+            .fd(fd);
+        info = info.push_next(&mut info_import_fd);
+    }
+
+    let mut info_import_host_pointer;
+
+    if let Some((handle_type, host_pointer)) = ext.import_host_pointer {
+        info_import_host_pointer = vk::ImportMemoryHostPointerInfoEXT {
+            handle_type,
+            host_pointer: host_pointer.as_ptr().cast(),
+            ..Default::default()
+        };
+        info = info.push_next(&mut info_import_host_pointer);
+    }
+
+    #[cfg(feature = "win32")]
+    let mut info_export_win32;
+
+    #[cfg(feature = "win32")]
+    if let Some(export_win32) = ext.export_win32 {
+        info_export_win32 = vk::ExportMemoryWin32HandleInfoKHR {
+            next: std::ptr::null(),
+            ..export_win32
+        };
+        info = info.push_next(&mut info_export_win32);
+    }
+
+    f(&info)
+}
+
+/// Returns types of the structures chained into `info`, in order.
+#[cfg(test)]
+pub(crate) fn chain_types(info: &impl vk::InputChainStruct) -> Vec<vk::StructureType> {
+    unsafe { vulkanalia::chain::input_chain(info.next()) }
+        .map(|ptr| unsafe { ptr.as_base_ref() }.s_type)
+        .collect()
+}
+
+/// Returns a copy of the first structure of type `T` chained into `info`.
+#[cfg(test)]
+pub(crate) fn find_in_chain<T: vk::InputChainStruct + Copy>(
+    info: &impl vk::InputChainStruct,
+) -> Option<T> {
+    unsafe { vulkanalia::chain::input_chain(info.next()) }
+        .find(|ptr| unsafe { ptr.as_base_ref() }.s_type == T::TYPE)
+        .map(|ptr| unsafe { *ptr.as_ref::<T>() })
+}
+
+/// Fails with [`VulkanaliaMemoryError::NotProtected`] unless the memory type is protected.
+fn protected_check(
+    properties: &DeviceProperties<'_>,
+    memory_type: u32,
+) -> Result<(), VulkanaliaMemoryError> {
+    if memory_type_props(properties, memory_type).contains(MemoryPropertyFlags::PROTECTED) {
+        Ok(())
+    } else {
+        Err(VulkanaliaMemoryError::NotProtected)
+    }
+}
+
+/// Returns flags this crate does not know how to translate,
+/// which are ignored by [`memory_allocate_flags`].
+#[cfg(any(test, feature = "tracing", feature = "log"))]
+fn unsupported_allocation_flags(flags: AllocationFlags) -> AllocationFlags {
+    flags.difference(AllocationFlags::DEVICE_ADDRESS)
+}
+
+/// Returns `VkMemoryAllocateFlagsInfo::flags` required by `flags` and `ext`.
+///
+/// `AllocationFlags` other than `DEVICE_ADDRESS` are ignored.
+fn memory_allocate_flags(
+    flags: AllocationFlags,
+    ext: &AllocateExtensions<'_>,
+) -> vk::MemoryAllocateFlags {
+    let mut allocate_flags = vk::MemoryAllocateFlags::empty();
+    if flags.contains(AllocationFlags::DEVICE_ADDRESS) {
+        allocate_flags |= vk::MemoryAllocateFlags::DEVICE_ADDRESS;
+    }
+    if ext.capture_replay || ext.opaque_capture_address != 0 {
+        allocate_flags |= vk::MemoryAllocateFlags::DEVICE_ADDRESS
+            | vk::MemoryAllocateFlags::DEVICE_ADDRESS_CAPTURE_REPLAY;
+    }
+
+    if ext.device_mask.is_some() {
+        allocate_flags |= vk::MemoryAllocateFlags::DEVICE_MASK;
+    }
+    allocate_flags
+}
+
+/// Allocates `total_size` bytes with `allocate` in segments of at most `max_segment` bytes,
+/// freeing allocated segments with `free` if any allocation fails.
+///
+/// Zero `max_segment` allocates a single segment.
+fn allocate_segments<M, E>(
+    total_size: u64,
+    max_segment: u64,
+    mut allocate: impl FnMut(u64) -> Result<M, E>,
+    mut free: impl FnMut(M),
+) -> Result<Vec<M>, E> {
+    let max_segment = if max_segment == 0 {
+        total_size
+    } else {
+        max_segment
+    };
+
+    let mut segments = Vec::new();
+    let mut remaining = total_size;
+    while remaining > 0 {
+        let size = remaining.min(max_segment);
+        match allocate(size) {
+            Ok(memory) => segments.push(memory),
+            Err(e) => {
+                segments.into_iter().for_each(&mut free);
+                return Err(e);
+            }
+        }
+        remaining -= size;
+    }
+
+    Ok(segments)
+}
+
+impl VulkanaliaMemoryDevice {
+    /// Allocates new memory object with a priority hint for the driver.
+    ///
+    /// When `priority` is `Some`, `VkMemoryPriorityAllocateInfoEXT` is chained into
+    /// the allocate info. The value is clamped to `[0.0, 1.0]`, NaN is replaced
+    /// with the default priority `0.5`.
+    ///
+    /// # Safety
+    ///
+    /// Same as for [`MemoryDevice::allocate_memory`](gpu_alloc_types::MemoryDevice::allocate_memory).
+    /// When `priority` is `Some`, extension "VK_EXT_memory_priority" and its
+    /// `memoryPriority` feature must be enabled on device creation.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(target = "gpu_alloc::vulkanalia", skip(self))
+    )]
+    pub unsafe fn allocate_memory_with_priority(
+        &self,
+        size: u64,
+        memory_type: u32,
+        flags: AllocationFlags,
+        priority: Option<f32>,
+    ) -> Result<vk::DeviceMemory, VulkanaliaMemoryError> {
+        let ext = AllocateExtensions {
+            priority,
+            ..Default::default()
+        };
+        self.allocate_memory_with(size, memory_type, flags, &ext)
+    }
+
+    /// Allocates new memory object on a subset of physical devices of a device group.
+    ///
+    /// Sets `DEVICE_MASK` allocate flag and `VkMemoryAllocateFlagsInfo::deviceMask`,
+    /// where bit `i` selects physical device `i` of the group.
+    ///
+    /// # Safety
+    ///
+    /// Same as for [`MemoryDevice::allocate_memory`](gpu_alloc_types::MemoryDevice::allocate_memory).
+    /// In addition:
+    /// - Vulkan 1.1 or extension "VK_KHR_device_group" must be enabled and the device
+    ///   must be created from a device group with `VkDeviceGroupDeviceCreateInfo`.
+    /// - `device_mask` must be nonzero and only contain bits of physical devices in the group.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(target = "gpu_alloc::vulkanalia", skip(self))
+    )]
+    pub unsafe fn allocate_memory_device_mask(
+        &self,
+        size: u64,
+        memory_type: u32,
+        flags: AllocationFlags,
+        device_mask: u32,
+    ) -> Result<vk::DeviceMemory, VulkanaliaMemoryError> {
+        let ext = AllocateExtensions {
+            device_mask: Some(device_mask),
+            ..Default::default()
+        };
+        self.allocate_memory_with(size, memory_type, flags, &ext)
+    }
+
+    /// Allocates new memory object dedicated to a single buffer or image.
+    ///
+    /// Chains `VkMemoryDedicatedAllocateInfo` into the allocate info, alongside
+    /// `VkMemoryAllocateFlagsInfo` when `flags` require it.
+    ///
+    /// # Safety
+    ///
+    /// Same as for [`MemoryDevice::allocate_memory`](gpu_alloc_types::MemoryDevice::allocate_memory).
+    /// In addition:
+    /// - Vulkan 1.1 or extension "VK_KHR_dedicated_allocation" must be enabled.
+    /// - `size` must be equal to the memory requirements size of the `dedicated` resource.
+    /// - The `dedicated` resource must not be bound to memory yet.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(target = "gpu_alloc::vulkanalia", skip(self))
+    )]
+    pub unsafe fn allocate_dedicated_memory(
+        &self,
+        size: u64,
+        memory_type: u32,
+        flags: AllocationFlags,
+        dedicated: DedicatedTarget,
+    ) -> Result<vk::DeviceMemory, VulkanaliaMemoryError> {
+        self.allocate_memory_with(size, memory_type, flags, &dedicated_extensions(dedicated))
+    }
+
+    /// Allocates new memory object sized for the `buffer` and binds the buffer to it.
+    ///
+    /// The memory object is not pooled and only holds this buffer,
+    /// which suits one-off resources, but wastes memory and allocation count
+    /// when used for many small ones. `VkMemoryDedicatedAllocateInfo` is chained
+    /// into the allocate info, like [`allocate_dedicated_memory`](Self::allocate_dedicated_memory) does.
+    /// Returns [`VulkanaliaMemoryError::IncompatibleMemoryType`] without allocating
+    /// when memory requirements of the buffer do not allow `memory_type`.
+    /// If binding fails, the memory is freed before returning the error.
+    ///
+    /// # Safety
+    ///
+    /// Same as for [`MemoryDevice::allocate_memory`](gpu_alloc_types::MemoryDevice::allocate_memory).
+    /// In addition:
+    /// - Vulkan 1.1 or extension "VK_KHR_dedicated_allocation" must be enabled.
+    /// - `buffer` must be created from this device and must not be bound to memory yet.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(target = "gpu_alloc::vulkanalia", skip(self))
+    )]
+    pub unsafe fn allocate_and_bind_buffer(
+        &self,
+        buffer: vk::Buffer,
+        memory_type: u32,
+        flags: AllocationFlags,
+    ) -> Result<vk::DeviceMemory, VulkanaliaMemoryError> {
+        let requirements = self.device.get_buffer_memory_requirements(buffer);
+        if memory_type >= u32::BITS || requirements.memory_type_bits & (1 << memory_type) == 0 {
+            return Err(VulkanaliaMemoryError::IncompatibleMemoryType);
+        }
+
+        let ext = dedicated_extensions(DedicatedTarget::Buffer(buffer));
+        let memory = self.allocate_memory_with(requirements.size, memory_type, flags, &ext)?;
+
+        bind_or_free(
+            memory,
+            |memory| {
+                self.device
+                    .bind_buffer_memory(buffer, memory, 0)
+                    .map_err(VulkanaliaMemoryError::from)
+            },
+            |memory| self.device.free_memory(memory, None),
+        )
+    }
+
+    /// Allocates new memory object and fills it with zeros.
+    ///
+    /// Vulkan does not guarantee the contents of new allocations, so the memory
+    /// is mapped, zeroed, flushed if the memory type is not host coherent and unmapped.
+    /// This touches every byte of the allocation from the host,
+    /// so it is only reasonable for small or security-sensitive allocations.
+    ///
+    /// Returns [`VulkanaliaMemoryError::NotHostVisible`] without allocating when
+    /// the memory type is not host visible.
+    ///
+    /// # Safety
+    ///
+    /// Same as for [`MemoryDevice::allocate_memory`](gpu_alloc_types::MemoryDevice::allocate_memory).
+    /// `properties` must be collected for the physical device of this device.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(target = "gpu_alloc::vulkanalia", skip(self, properties))
+    )]
+    pub unsafe fn allocate_zeroed_memory(
+        &self,
+        size: u64,
+        memory_type: u32,
+        flags: AllocationFlags,
+        properties: &DeviceProperties<'_>,
+    ) -> Result<vk::DeviceMemory, VulkanaliaMemoryError> {
+        let memory_flags = memory_type_props(properties, memory_type);
+
+        if !memory_flags.contains(MemoryPropertyFlags::HOST_VISIBLE) {
+            return Err(VulkanaliaMemoryError::NotHostVisible);
+        }
+        let len = usize::try_from(size).map_err(|_| VulkanaliaMemoryError::OutOfHostMemory)?;
+
+        let memory = self.allocate_memory_with(size, memory_type, flags, &Default::default())?;
+
+        let zeroed = (|| {
+            let ptr =
+                self.device
+                    .map_memory(memory, 0, vk::WHOLE_SIZE, vk::MemoryMapFlags::empty())?;
+            let ptr = self
+                .non_null_mapping(memory, ptr)
+                .ok_or(vk::ErrorCode::MEMORY_MAP_FAILED)?;
+            std::ptr::write_bytes(ptr.as_ptr(), 0, len);
+
+            let flushed = if memory_flags.contains(MemoryPropertyFlags::HOST_COHERENT) {
+                Ok(())
+            } else {
+                let range = vk::MappedMemoryRange::builder()
+                    .memory(memory)
+                    .offset(0)
+                    .size(vk::WHOLE_SIZE);
+                self.device.flush_mapped_memory_ranges(&[range])
+            };
+
+            self.device.unmap_memory(memory);
+            flushed
+        })();
+
+        match zeroed {
+            Ok(()) => Ok(memory),
+            Err(e) => {
+                self.device.free_memory(memory, None);
+                Err(e.into())
+            }
+        }
+    }
+
+    /// Allocates new memory object trying each of `memory_types` in order,
+    /// e.g. device-local types first and host-visible ones after them.
+    ///
+    /// Returns the memory object along with the memory type it was allocated from.
+    /// If all memory types fail the last error is returned,
+    /// an empty `memory_types` fails with [`VulkanaliaMemoryError::OutOfDeviceMemory`].
+    ///
+    /// # Safety
+    ///
+    /// Same as for [`MemoryDevice::allocate_memory`](gpu_alloc_types::MemoryDevice::allocate_memory)
+    /// for each of `memory_types`.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(target = "gpu_alloc::vulkanalia", skip(self))
+    )]
+    pub unsafe fn allocate_memory_fallback(
+        &self,
+        size: u64,
+        memory_types: &[u32],
+        flags: AllocationFlags,
+    ) -> Result<(vk::DeviceMemory, u32), VulkanaliaMemoryError> {
+        let ext = AllocateExtensions::default();
+
+        let mut last_error = VulkanaliaMemoryError::OutOfDeviceMemory;
+        for (index, &memory_type) in memory_types.iter().enumerate() {
+            match self.allocate_memory_with(size, memory_type, flags, &ext) {
+                Ok(memory) => {
+                    #[cfg(feature = "tracing")]
+                    if index > 0 {
+                        tracing::debug!(
+                            target: "gpu_alloc::vulkanalia",
+                            index,
+                            memory_type,
+                            "Allocated from fallback memory type"
+                        );
+                    }
+                    #[cfg(feature = "log")]
+                    if index > 0 {
+                        log::debug!(
+                            target: "gpu_alloc::vulkanalia",
+                            "Allocated from fallback memory type {memory_type} (index {index})"
+                        );
+                    }
+                    #[cfg(not(any(feature = "tracing", feature = "log")))]
+                    let _ = index;
+
+                    return Ok((memory, memory_type));
+                }
+                Err(e) => last_error = e,
+            }
+        }
+
+        Err(last_error)
+    }
+
+    /// Allocates new memory object from a protected memory type,
+    /// e.g. for DRM-protected video content.
+    ///
+    /// Protected memory needs no special allocate info, only a protected memory type,
+    /// so the type is validated to prevent treating protected content as normal memory.
+    /// Returns [`VulkanaliaMemoryError::NotProtected`] without allocating when
+    /// the memory type is not protected.
+    ///
+    /// # Safety
+    ///
+    /// Same as for [`MemoryDevice::allocate_memory`](gpu_alloc_types::MemoryDevice::allocate_memory).
+    /// In addition:
+    /// - Vulkan 1.1 must be used and the `protectedMemory` feature must be enabled.
+    /// - `properties` must be collected for the physical device of this device.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(target = "gpu_alloc::vulkanalia", skip(self, properties))
+    )]
+    pub unsafe fn allocate_protected_memory(
+        &self,
+        size: u64,
+        memory_type: u32,
+        flags: AllocationFlags,
+        properties: &DeviceProperties<'_>,
+    ) -> Result<vk::DeviceMemory, VulkanaliaMemoryError> {
+        protected_check(properties, memory_type)?;
+        self.allocate_memory_with(size, memory_type, flags, &Default::default())
+    }
+
+    /// Allocates new memory object with enough padding to place `size` bytes
+    /// at any `alignment` boundary within it.
+    ///
+    /// Allocates `size + alignment` bytes, so up to `alignment` bytes are wasted.
+    /// Use [`AlignedMemory::offset_for`] to find the aligned offset once
+    /// the base address of the memory is known.
+    /// Zero `alignment` is treated as 1.
+    ///
+    /// # Safety
+    ///
+    /// Same as for [`MemoryDevice::allocate_memory`](gpu_alloc_types::MemoryDevice::allocate_memory),
+    /// with `size + alignment` as the size.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(target = "gpu_alloc::vulkanalia", skip(self))
+    )]
+    pub unsafe fn allocate_aligned_memory(
+        &self,
+        size: u64,
+        memory_type: u32,
+        flags: AllocationFlags,
+        alignment: u64,
+    ) -> Result<AlignedMemory, VulkanaliaMemoryError> {
+        let alignment = alignment.max(1);
+        let allocation_size = size
+            .checked_add(alignment)
+            .ok_or(VulkanaliaMemoryError::OutOfDeviceMemory)?;
+
+        let memory =
+            self.allocate_memory_with(allocation_size, memory_type, flags, &Default::default())?;
+
+        Ok(AlignedMemory {
+            memory,
+            allocation_size,
+            alignment,
+        })
+    }
+
+    /// Allocates several memory objects at once, e.g. to warm up a pool.
+    ///
+    /// Each request is a tuple of `size`, `memory_type` and `flags` as passed to
+    /// [`MemoryDevice::allocate_memory`](gpu_alloc_types::MemoryDevice::allocate_memory).
+    /// A failed request does not abort the batch, results are returned in
+    /// the order of `requests`.
+    ///
+    /// Requires `smallvec` feature.
+    ///
+    /// # Safety
+    ///
+    /// Same as for [`MemoryDevice::allocate_memory`](gpu_alloc_types::MemoryDevice::allocate_memory)
+    /// for each request.
+    #[cfg(feature = "smallvec")]
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            target = "gpu_alloc::vulkanalia",
+            skip(self, requests),
+            fields(requests = requests.len())
+        )
+    )]
+    pub unsafe fn allocate_memory_batch(
+        &self,
+        requests: &[(u64, u32, AllocationFlags)],
+    ) -> SmallVec<[Result<vk::DeviceMemory, VulkanaliaMemoryError>; 8]> {
+        let ext = AllocateExtensions::default();
+        allocate_batch(requests, |size, memory_type, flags| {
+            self.allocate_memory_with(size, memory_type, flags, &ext)
+        })
+    }
+
+    /// Allocates `total_size` bytes as several memory objects of at most `max_segment` bytes,
+    /// e.g. to back a sparse resource larger than `max_memory_allocation_size`.
+    ///
+    /// All segments except the last one are exactly `max_segment` bytes,
+    /// the last one holds the remainder. Zero `max_segment` uses `max_memory_allocation_size`
+    /// of `properties`, falling back to a single segment when they are not known.
+    /// Zero `total_size` allocates nothing.
+    /// If any segment fails to allocate, already allocated ones are freed.
+    /// The caller binds resources to each segment on their own.
+    ///
+    /// # Safety
+    ///
+    /// Same as for [`MemoryDevice::allocate_memory`](gpu_alloc_types::MemoryDevice::allocate_memory)
+    /// for each segment.
+    /// `properties` must be collected for the physical device of this device.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(target = "gpu_alloc::vulkanalia", skip(self, properties))
+    )]
+    pub unsafe fn allocate_segmented_memory(
+        &self,
+        total_size: u64,
+        memory_type: u32,
+        flags: AllocationFlags,
+        max_segment: u64,
+        properties: Option<&DeviceProperties<'_>>,
+    ) -> Result<Vec<vk::DeviceMemory>, VulkanaliaMemoryError> {
+        let max_segment = match (max_segment, properties) {
+            (0, Some(properties)) => properties.max_memory_allocation_size,
+            (max_segment, _) => max_segment,
+        };
+
+        let ext = AllocateExtensions::default();
+        allocate_segments(
+            total_size,
+            max_segment,
+            |size| self.allocate_memory_with(size, memory_type, flags, &ext),
+            |memory| self.device.free_memory(memory, None),
+        )
+    }
+
+    /// Allocates new memory object with a caller-built chain of extension structures,
+    /// for combinations the other methods do not cover.
+    ///
+    /// `p_next` is linked into `VkMemoryAllocateInfo` after `VkMemoryAllocateFlagsInfo`,
+    /// which is still chained when `flags` require it. Null `p_next` is allowed
+    /// and makes this the same as [`try_allocate_memory`](Self::try_allocate_memory).
+    ///
+    /// # Safety
+    ///
+    /// Same as for [`MemoryDevice::allocate_memory`](gpu_alloc_types::MemoryDevice::allocate_memory).
+    /// In addition:
+    /// - `p_next` must be null or point to a valid chain of structures extending
+    ///   `VkMemoryAllocateInfo`, terminated by a null `pNext`. Every structure and
+    ///   everything it points to must stay valid until this call returns.
+    /// - The chain must not contain `VkMemoryAllocateFlagsInfo` when `flags` are not empty,
+    ///   and the extensions and features of its structures must be enabled.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(target = "gpu_alloc::vulkanalia", skip(self))
+    )]
+    pub unsafe fn allocate_memory_raw(
+        &self,
+        size: u64,
+        memory_type: u32,
+        flags: AllocationFlags,
+        p_next: *const c_void,
+    ) -> Result<vk::DeviceMemory, VulkanaliaMemoryError> {
+        let ext = AllocateExtensions {
+            next: NonNull::new(p_next.cast_mut()),
+            ..Default::default()
+        };
+        self.allocate_memory_with(size, memory_type, flags, &ext)
+    }
+
+    pub(crate) unsafe fn allocate_memory_with(
+        &self,
+        size: u64,
+        memory_type: u32,
+        flags: AllocationFlags,
+        ext: &AllocateExtensions<'_>,
+    ) -> Result<vk::DeviceMemory, VulkanaliaMemoryError> {
+        // Flags this crate does not know how to translate are ignored
+        #[cfg(any(feature = "tracing", feature = "log"))]
+        {
+            let unsupported = unsupported_allocation_flags(flags);
+            if !unsupported.is_empty() {
+                #[cfg(feature = "tracing")]
+                tracing::warn!(
+                    target: "gpu_alloc::vulkanalia",
+                    ?unsupported,
+                    "Ignoring unsupported allocation flags"
+                );
+                #[cfg(feature = "log")]
+                log::warn!(
+                    target: "gpu_alloc::vulkanalia",
+                    "Ignoring unsupported allocation flags {unsupported:?}"
+                );
+            }
+        }
+
+        let memory = with_allocate_info(size, memory_type, flags, ext, |info| {
+            self.device.allocate_memory(info, ext.allocation_callbacks)
+        })
+        .map_err(VulkanaliaMemoryError::from)?;
+
+        #[cfg(feature = "tracing")]
+        tracing::trace!(target: "gpu_alloc::vulkanalia", ?memory, "Memory allocated");
+        #[cfg(feature = "log")]
+        log::trace!(target: "gpu_alloc::vulkanalia", "Memory {memory:?} allocated");
+
+        Ok(memory)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(feature = "mock")]
+    use gpu_alloc_types::MemoryDevice;
+    use gpu_alloc_types::{MemoryHeap, MemoryType};
+
+    use super::*;
+    #[cfg(feature = "mock")]
+    use crate::MockMemoryDevice;
+
+    fn aligned_memory(alignment: u64) -> AlignedMemory {
+        AlignedMemory {
+            memory: vk::DeviceMemory::null(),
+            allocation_size: 4096 + alignment,
+            alignment,
+        }
+    }
+
+    #[test]
+    fn offset_for_aligned_base_is_zero() {
+        let memory = aligned_memory(256);
+
+        assert_eq!(memory.offset_for(0), 0);
+        assert_eq!(memory.offset_for(0x1_0000), 0);
+    }
+
+    #[test]
+    fn offset_for_misaligned_base_reaches_next_boundary() {
+        let memory = aligned_memory(256);
+
+        assert_eq!(memory.offset_for(1), 255);
+        assert_eq!(memory.offset_for(0x1_0010), 0xf0);
+        assert_eq!(memory.offset_for(u64::MAX), 1);
+    }
+
+    #[test]
+    fn offset_for_alignment_one_is_zero() {
+        for alignment in [0, 1] {
+            let memory = aligned_memory(alignment);
+
+            assert_eq!(memory.offset_for(0), 0);
+            assert_eq!(memory.offset_for(7), 0);
+            assert_eq!(memory.offset_for(u64::MAX), 0);
+        }
+    }
+
+    #[test]
+    fn clamp_priority_keeps_boundaries() {
+        assert_eq!(clamp_priority(0.0), 0.0);
+        assert_eq!(clamp_priority(0.25), 0.25);
+        assert_eq!(clamp_priority(1.0), 1.0);
+    }
+
+    #[test]
+    fn clamp_priority_clamps_out_of_range_values() {
+        assert_eq!(clamp_priority(-0.5), 0.0);
+        assert_eq!(clamp_priority(f32::NEG_INFINITY), 0.0);
+        assert_eq!(clamp_priority(1.5), 1.0);
+        assert_eq!(clamp_priority(f32::INFINITY), 1.0);
+    }
+
+    #[test]
+    fn clamp_priority_replaces_nan_with_default() {
+        assert_eq!(clamp_priority(f32::NAN), 0.5);
+    }
+
+    #[test]
+    fn priority_is_chained_clamped() {
+        for (priority, chained) in [(0.75, 0.75), (2.0, 1.0), (f32::NAN, 0.5)] {
+            let ext = AllocateExtensions {
+                priority: Some(priority),
+                ..Default::default()
+            };
+
+            let (types, info) =
+                with_allocate_info(256, 0, AllocationFlags::empty(), &ext, |info| {
+                    (
+                        chain_types(info),
+                        find_in_chain::<vk::MemoryPriorityAllocateInfoEXT>(info),
+                    )
+                });
+
+            assert_eq!(
+                types,
+                [vk::StructureType::MEMORY_PRIORITY_ALLOCATE_INFO_EXT]
+            );
+            assert_eq!(info.unwrap().priority, chained);
+        }
+    }
+
+    #[test]
+    fn priority_is_not_chained_by_default() {
+        let types = with_allocate_info(
+            256,
+            0,
+            AllocationFlags::empty(),
+            &AllocateExtensions::default(),
+            chain_types,
+        );
+
+        assert!(types.is_empty());
+    }
+
+    #[test]
+    fn dedicated_buffer_is_chained() {
+        let buffer = vk::Buffer::from_raw(5);
+        let ext = dedicated_extensions(DedicatedTarget::Buffer(buffer));
+
+        let (types, dedicated) =
+            with_allocate_info(256, 0, AllocationFlags::empty(), &ext, |info| {
+                (
+                    chain_types(info),
+                    find_in_chain::<vk::MemoryDedicatedAllocateInfo>(info),
+                )
+            });
+
+        assert_eq!(types, [vk::StructureType::MEMORY_DEDICATED_ALLOCATE_INFO]);
+        let dedicated = dedicated.unwrap();
+        assert_eq!(dedicated.buffer, buffer);
+        assert_eq!(dedicated.image, vk::Image::null());
+    }
+
+    #[cfg(feature = "mock")]
+    #[test]
+    fn bind_failure_frees_memory() {
+        let device = MockMemoryDevice::new();
+
+        let result = unsafe {
+            let memory = device
+                .allocate_memory(256, 0, AllocationFlags::empty())
+                .unwrap();
+            bind_or_free(
+                memory,
+                |_| Err(VulkanaliaMemoryError::OutOfDeviceMemory),
+                |memory| device.deallocate_memory(memory),
+            )
+        };
+
+        assert!(matches!(
+            result,
+            Err(VulkanaliaMemoryError::OutOfDeviceMemory)
+        ));
+        assert_eq!(device.calls().deallocate, 1);
+        assert_eq!(device.live_count(), 0);
+    }
+
+    #[cfg(feature = "mock")]
+    #[test]
+    fn bound_memory_is_kept() {
+        let device = MockMemoryDevice::new();
+
+        let memory = unsafe {
+            let memory = device
+                .allocate_memory(256, 0, AllocationFlags::empty())
+                .unwrap();
+            bind_or_free(
+                memory,
+                |_| Ok::<_, VulkanaliaMemoryError>(()),
+                |memory| device.deallocate_memory(memory),
+            )
+        }
+        .unwrap();
+
+        assert_eq!(device.calls().deallocate, 0);
+        assert_eq!(device.allocation_size(memory), Some(256));
+        unsafe { device.deallocate_memory(memory) };
+    }
+
+    #[test]
+    fn protected_check_rejects_unprotected_types() {
+        let memory_type = |props| MemoryType { props, heap: 0 };
+        let properties = DeviceProperties {
+            memory_types: vec![
+                memory_type(MemoryPropertyFlags::DEVICE_LOCAL),
+                memory_type(MemoryPropertyFlags::DEVICE_LOCAL | MemoryPropertyFlags::PROTECTED),
+            ]
+            .into(),
+            memory_heaps: vec![MemoryHeap { size: 1 << 30 }].into(),
+            max_memory_allocation_count: 4096,
+            max_memory_allocation_size: u64::MAX,
+            non_coherent_atom_size: 64,
+            buffer_device_address: false,
+        };
+
+        assert_eq!(
+            protected_check(&properties, 0),
+            Err(VulkanaliaMemoryError::NotProtected)
+        );
+        assert_eq!(protected_check(&properties, 1), Ok(()));
+        assert_eq!(
+            protected_check(&properties, 2),
+            Err(VulkanaliaMemoryError::NotProtected)
+        );
+    }
+
+    #[test]
+    fn allocate_flags_for_device_address() {
+        let ext = AllocateExtensions::default();
+        assert_eq!(
+            memory_allocate_flags(AllocationFlags::empty(), &ext),
+            vk::MemoryAllocateFlags::empty()
+        );
+        assert_eq!(
+            memory_allocate_flags(AllocationFlags::DEVICE_ADDRESS, &ext),
+            vk::MemoryAllocateFlags::DEVICE_ADDRESS
+        );
+    }
+
+    #[test]
+    fn allocate_flags_ignore_unsupported_flags() {
+        let unknown = AllocationFlags::from_bits_retain(0x80);
+        let ext = AllocateExtensions::default();
+
+        assert_eq!(unsupported_allocation_flags(unknown), unknown);
+        assert_eq!(
+            unsupported_allocation_flags(unknown | AllocationFlags::DEVICE_ADDRESS),
+            unknown
+        );
+        assert!(unsupported_allocation_flags(AllocationFlags::DEVICE_ADDRESS).is_empty());
+
+        assert_eq!(
+            memory_allocate_flags(unknown, &ext),
+            vk::MemoryAllocateFlags::empty()
+        );
+        assert_eq!(
+            memory_allocate_flags(unknown | AllocationFlags::DEVICE_ADDRESS, &ext),
+            vk::MemoryAllocateFlags::DEVICE_ADDRESS
+        );
+    }
+
+    #[test]
+    fn allocate_flags_for_capture_replay() {
+        let capture_replay = vk::MemoryAllocateFlags::DEVICE_ADDRESS
+            | vk::MemoryAllocateFlags::DEVICE_ADDRESS_CAPTURE_REPLAY;
+
+        for (flags, capture, address) in [
+            (AllocationFlags::empty(), true, 0),
+            (AllocationFlags::empty(), false, 0x1000),
+            (AllocationFlags::empty(), true, 0x1000),
+            (AllocationFlags::DEVICE_ADDRESS, true, 0),
+            (AllocationFlags::DEVICE_ADDRESS, false, 0x1000),
+        ] {
+            let ext = AllocateExtensions {
+                capture_replay: capture,
+                opaque_capture_address: address,
+                ..Default::default()
+            };
+            assert_eq!(memory_allocate_flags(flags, &ext), capture_replay);
+        }
+    }
+
+    #[test]
+    fn allocate_flags_for_device_mask() {
+        let ext = AllocateExtensions {
+            device_mask: Some(0b10),
+            ..Default::default()
+        };
+        assert_eq!(
+            memory_allocate_flags(AllocationFlags::empty(), &ext),
+            vk::MemoryAllocateFlags::DEVICE_MASK
+        );
+        assert_eq!(
+            memory_allocate_flags(AllocationFlags::DEVICE_ADDRESS, &ext),
+            vk::MemoryAllocateFlags::DEVICE_MASK | vk::MemoryAllocateFlags::DEVICE_ADDRESS
+        );
+    }
+
+    #[cfg(all(feature = "mock", feature = "smallvec"))]
+    #[test]
+    fn batch_keeps_results_of_each_request() {
+        let device = MockMemoryDevice::new().with_allocation_limit(1);
+        let requests = [
+            (64, 0, AllocationFlags::empty()),
+            (128, 0, AllocationFlags::empty()),
+        ];
+
+        let results = allocate_batch(&requests, |size, memory_type, flags| unsafe {
+            device.allocate_memory(size, memory_type, flags)
+        });
+
+        assert_eq!(results.len(), 2);
+        let memory = *results[0].as_ref().unwrap();
+        assert_eq!(device.allocation_size(memory), Some(64));
+        assert!(results[1].is_err());
+        assert_eq!(device.live_count(), 1);
+
+        unsafe { device.deallocate_memory(memory) };
+    }
+
+    #[cfg(feature = "mock")]
+    fn segment_sizes(device: &MockMemoryDevice, segments: &[u64]) -> Vec<u64> {
+        segments
+            .iter()
+            .map(|&memory| device.allocation_size(memory).unwrap())
+            .collect()
+    }
+
+    #[cfg(feature = "mock")]
+    unsafe fn allocate_mock_segments(
+        device: &MockMemoryDevice,
+        total_size: u64,
+        max_segment: u64,
+    ) -> Result<Vec<u64>, gpu_alloc_types::OutOfMemory> {
+        allocate_segments(
+            total_size,
+            max_segment,
+            |size| device.allocate_memory(size, 0, AllocationFlags::empty()),
+            |memory| device.deallocate_memory(memory),
+        )
+    }
+
+    #[cfg(feature = "mock")]
+    #[test]
+    fn segments_split_total_size() {
+        let device = MockMemoryDevice::new();
+
+        unsafe {
+            for (total_size, max_segment, expected) in [
+                (300, 100, &[100, 100, 100][..]),
+                (250, 100, &[100, 100, 50]),
+                (250, 0, &[250]),
+                (50, 100, &[50]),
+                (0, 100, &[]),
+                (0, 0, &[]),
+            ] {
+                let segments = allocate_mock_segments(&device, total_size, max_segment).unwrap();
+                assert_eq!(segment_sizes(&device, &segments), expected);
+                segments
+                    .into_iter()
+                    .for_each(|memory| device.deallocate_memory(memory));
+            }
+        }
+
+        assert_eq!(device.live_count(), 0);
+    }
+
+    #[cfg(feature = "mock")]
+    #[test]
+    fn segments_are_freed_on_failure() {
+        let device = MockMemoryDevice::new().with_allocation_limit(2);
+
+        unsafe {
+            assert!(allocate_mock_segments(&device, 250, 100).is_err());
+        }
+
+        assert_eq!(device.live_count(), 0);
+        assert_eq!(device.calls().allocate, 3);
+        assert_eq!(device.calls().deallocate, 2);
+    }
+}
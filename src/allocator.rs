@@ -0,0 +1,266 @@
+use std::ptr::NonNull;
+
+use gpu_alloc::{Config, GpuAllocator, MemoryBlock, UsageFlags};
+use vulkanalia::prelude::v1_0::*;
+
+use crate::{device_properties, VulkanaliaMemoryDevice, VulkanaliaMemoryError};
+
+/// Creates a [`GpuAllocator`] for the specified physical device,
+/// collecting its properties with [`device_properties`].
+///
+/// # Safety
+///
+/// Same as for [`device_properties`]. Since the queried properties are passed
+/// to the allocator as is, the `bufferDeviceAddress` feature must be enabled on device
+/// creation whenever the physical device supports it. Otherwise collect the properties
+/// with [`DevicePropertiesBuilder::force_buffer_device_address`] set to `false`
+/// and create the allocator with [`GpuAllocator::new`] instead.
+///
+/// [`DevicePropertiesBuilder::force_buffer_device_address`]: crate::DevicePropertiesBuilder::force_buffer_device_address
+pub unsafe fn create_allocator(
+    instance: &Instance,
+    version: u32,
+    physical_device: vk::PhysicalDevice,
+    config: Config,
+) -> VkResult<GpuAllocator<vk::DeviceMemory>> {
+    let properties = device_properties(instance, version, physical_device)?;
+    Ok(GpuAllocator::new(config, properties))
+}
+
+/// Creates a [`GpuAllocator`] like [`create_allocator`] does,
+/// using [`Config::i_am_prototyping`] configuration.
+///
+/// # Safety
+///
+/// Same as for [`create_allocator`].
+pub unsafe fn create_allocator_with_defaults(
+    instance: &Instance,
+    version: u32,
+    physical_device: vk::PhysicalDevice,
+) -> VkResult<GpuAllocator<vk::DeviceMemory>> {
+    create_allocator(
+        instance,
+        version,
+        physical_device,
+        Config::i_am_prototyping(),
+    )
+}
+
+/// Binds `buffer` to the memory of the `block` at the block's offset.
+///
+/// # Safety
+///
+/// - `buffer` and the `block` must be created from the `device`.
+/// - `buffer` must not be bound to memory yet.
+/// - The `block` must satisfy memory requirements of the `buffer`.
+pub unsafe fn bind_buffer(
+    device: &Device,
+    buffer: vk::Buffer,
+    block: &MemoryBlock<vk::DeviceMemory>,
+) -> VkResult<()> {
+    device.bind_buffer_memory(buffer, *block.memory(), block.offset())
+}
+
+/// Binds `image` to the memory of the `block` at the block's offset.
+///
+/// # Safety
+///
+/// - `image` and the `block` must be created from the `device`.
+/// - `image` must not be bound to memory yet.
+/// - The `block` must satisfy memory requirements of the `image`.
+pub unsafe fn bind_image(
+    device: &Device,
+    image: vk::Image,
+    block: &MemoryBlock<vk::DeviceMemory>,
+) -> VkResult<()> {
+    device.bind_image_memory(image, *block.memory(), block.offset())
+}
+
+/// Maps `size` bytes of the `block` starting at `offset` relative to the block.
+///
+/// The block's own offset within its memory object is added, so `offset` zero
+/// is the start of the block. Returns [`VulkanaliaMemoryError::OutOfBounds`]
+/// without mapping anything when the range is empty or does not fit into the block,
+/// so `vk::WHOLE_SIZE` is rejected too.
+///
+/// Unlike [`MemoryBlock::map`], this does not track the mapping in the block.
+/// Unmap it with `vkUnmapMemory` on the block's memory object.
+///
+/// # Safety
+///
+/// - The `block` must be created from the `device` and be host visible.
+/// - The memory object of the `block` must not be mapped, including through
+///   [`MemoryBlock::map`] of this or any other block sharing it.
+pub unsafe fn map_block(
+    device: &Device,
+    block: &MemoryBlock<vk::DeviceMemory>,
+    offset: u64,
+    size: u64,
+) -> Result<NonNull<u8>, VulkanaliaMemoryError> {
+    let map_offset = block_map_offset(block.offset(), block.size(), offset, size)?;
+
+    let memory = *block.memory();
+    let ptr = device
+        .map_memory(memory, map_offset, size, vk::MemoryMapFlags::empty())
+        .map_err(VulkanaliaMemoryError::from)?;
+
+    VulkanaliaMemoryDevice::wrap(device)
+        .non_null_mapping(memory, ptr)
+        .ok_or(VulkanaliaMemoryError::MapFailed)
+}
+
+/// Returns offset within the memory object of `size` bytes at `offset` relative to a block
+/// at `block_offset` of `block_size` bytes.
+///
+/// Fails with [`VulkanaliaMemoryError::OutOfBounds`] when the range is empty
+/// or does not fit into the block.
+fn block_map_offset(
+    block_offset: u64,
+    block_size: u64,
+    offset: u64,
+    size: u64,
+) -> Result<u64, VulkanaliaMemoryError> {
+    match offset.checked_add(size) {
+        Some(end) if size != 0 && end <= block_size => block_offset
+            .checked_add(offset)
+            .ok_or(VulkanaliaMemoryError::OutOfBounds),
+        _ => Err(VulkanaliaMemoryError::OutOfBounds),
+    }
+}
+
+/// How the host accesses memory of a resource, see [`usage_flags_for`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum HostAccess {
+    /// The memory is never mapped.
+    #[default]
+    None,
+    /// The host writes the memory, e.g. staging or per-frame uniform buffers.
+    Write,
+    /// The host reads the memory, e.g. readback buffers.
+    Read,
+    /// The host both writes and reads the memory.
+    ReadWrite,
+}
+
+/// Picks [`UsageFlags`] for memory of a buffer with `buffer_usage`
+/// accessed from the host as described by `host_access`.
+///
+/// | `host_access` | Buffer usage                  | Usage flags                    |
+/// |---------------|-------------------------------|--------------------------------|
+/// | `None`        | any                           | `FAST_DEVICE_ACCESS`           |
+/// | `Write`       | only transfer source          | `UPLOAD`                       |
+/// | `Write`       | anything else                 | `UPLOAD \| FAST_DEVICE_ACCESS` |
+/// | `Read`        | any                           | `DOWNLOAD`                     |
+/// | `ReadWrite`   | any                           | `UPLOAD \| DOWNLOAD`           |
+///
+/// `DEVICE_ADDRESS` is added whenever `buffer_usage` contains `SHADER_DEVICE_ADDRESS`.
+/// Staging buffers are only copied from, so they don't need fast device access.
+/// Other host written buffers, like uniform buffers, are read by the device directly,
+/// so device local host visible memory is preferred when available.
+pub fn usage_flags_for(buffer_usage: vk::BufferUsageFlags, host_access: HostAccess) -> UsageFlags {
+    let mut flags = match host_access {
+        HostAccess::None => UsageFlags::FAST_DEVICE_ACCESS,
+        HostAccess::Write if buffer_usage == vk::BufferUsageFlags::TRANSFER_SRC => {
+            UsageFlags::UPLOAD
+        }
+        HostAccess::Write => UsageFlags::UPLOAD | UsageFlags::FAST_DEVICE_ACCESS,
+        HostAccess::Read => UsageFlags::DOWNLOAD,
+        HostAccess::ReadWrite => UsageFlags::UPLOAD | UsageFlags::DOWNLOAD,
+    };
+
+    if buffer_usage.contains(vk::BufferUsageFlags::SHADER_DEVICE_ADDRESS) {
+        flags |= UsageFlags::DEVICE_ADDRESS;
+    }
+    flags
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn usage_flags_follow_host_access() {
+        let storage = vk::BufferUsageFlags::STORAGE_BUFFER;
+        assert_eq!(
+            usage_flags_for(storage, HostAccess::None),
+            UsageFlags::FAST_DEVICE_ACCESS
+        );
+        assert_eq!(
+            usage_flags_for(storage, HostAccess::Write),
+            UsageFlags::UPLOAD | UsageFlags::FAST_DEVICE_ACCESS
+        );
+        assert_eq!(
+            usage_flags_for(storage, HostAccess::Read),
+            UsageFlags::DOWNLOAD
+        );
+        assert_eq!(
+            usage_flags_for(storage, HostAccess::ReadWrite),
+            UsageFlags::UPLOAD | UsageFlags::DOWNLOAD
+        );
+    }
+
+    #[test]
+    fn usage_flags_of_staging_buffers_skip_fast_device_access() {
+        assert_eq!(
+            usage_flags_for(vk::BufferUsageFlags::TRANSFER_SRC, HostAccess::Write),
+            UsageFlags::UPLOAD
+        );
+        assert_eq!(
+            usage_flags_for(
+                vk::BufferUsageFlags::TRANSFER_SRC | vk::BufferUsageFlags::UNIFORM_BUFFER,
+                HostAccess::Write
+            ),
+            UsageFlags::UPLOAD | UsageFlags::FAST_DEVICE_ACCESS
+        );
+    }
+
+    #[test]
+    fn usage_flags_add_device_address() {
+        let usage =
+            vk::BufferUsageFlags::STORAGE_BUFFER | vk::BufferUsageFlags::SHADER_DEVICE_ADDRESS;
+        assert_eq!(
+            usage_flags_for(usage, HostAccess::None),
+            UsageFlags::FAST_DEVICE_ACCESS | UsageFlags::DEVICE_ADDRESS
+        );
+        assert_eq!(
+            usage_flags_for(usage, HostAccess::Read),
+            UsageFlags::DOWNLOAD | UsageFlags::DEVICE_ADDRESS
+        );
+    }
+
+    #[test]
+    fn block_map_offset_adds_block_offset() {
+        assert_eq!(block_map_offset(0, 256, 0, 256).unwrap(), 0);
+        assert_eq!(block_map_offset(1024, 256, 0, 16).unwrap(), 1024);
+        assert_eq!(block_map_offset(1024, 256, 64, 16).unwrap(), 1088);
+        assert_eq!(block_map_offset(1024, 256, 192, 64).unwrap(), 1216);
+    }
+
+    #[test]
+    fn block_map_offset_rejects_ranges_outside_the_block() {
+        let out_of_bounds = |offset, size| {
+            matches!(
+                block_map_offset(1024, 256, offset, size),
+                Err(VulkanaliaMemoryError::OutOfBounds)
+            )
+        };
+
+        assert!(out_of_bounds(192, 65));
+        assert!(out_of_bounds(256, 1));
+        assert!(out_of_bounds(0, 257));
+        assert!(out_of_bounds(u64::MAX, 2));
+        assert!(out_of_bounds(0, vk::WHOLE_SIZE));
+    }
+
+    #[test]
+    fn block_map_offset_rejects_empty_ranges() {
+        assert!(matches!(
+            block_map_offset(1024, 256, 0, 0),
+            Err(VulkanaliaMemoryError::OutOfBounds)
+        ));
+        assert!(matches!(
+            block_map_offset(1024, 256, 256, 0),
+            Err(VulkanaliaMemoryError::OutOfBounds)
+        ));
+    }
+}
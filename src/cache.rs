@@ -0,0 +1,153 @@
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use gpu_alloc_types::DeviceProperties;
+use vulkanalia::prelude::v1_0::*;
+
+use crate::{device_properties, lock_ignoring_poison};
+
+/// Memoizes [`device_properties`] results per physical device.
+///
+/// Clones share the same storage, so a cache can be cheaply passed around
+/// and reused when the allocator is recreated, e.g. after a device loss.
+#[derive(Clone, Debug, Default)]
+pub struct DevicePropertiesCache {
+    entries: Arc<Mutex<HashMap<vk::PhysicalDevice, DeviceProperties<'static>>>>,
+}
+
+impl DevicePropertiesCache {
+    /// Creates an empty cache.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns cached properties of the `physical_device`,
+    /// querying them with [`device_properties`] on first access.
+    ///
+    /// The cache stays locked during the query, so concurrent first accesses
+    /// for any physical device wait for it instead of querying again.
+    /// Failed queries are not cached.
+    ///
+    /// # Safety
+    ///
+    /// Same as for [`device_properties`].
+    /// The cache must only be used with a single `instance` and `version`,
+    /// since they are not part of the cache key.
+    pub unsafe fn get_or_query(
+        &self,
+        instance: &Instance,
+        version: u32,
+        physical_device: vk::PhysicalDevice,
+    ) -> VkResult<DeviceProperties<'static>> {
+        self.get_or_insert_with(physical_device, || {
+            device_properties(instance, version, physical_device)
+        })
+    }
+
+    fn get_or_insert_with<E>(
+        &self,
+        physical_device: vk::PhysicalDevice,
+        query: impl FnOnce() -> Result<DeviceProperties<'static>, E>,
+    ) -> Result<DeviceProperties<'static>, E> {
+        let mut entries = self.lock();
+        let properties = match entries.entry(physical_device) {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => entry.insert(query()?),
+        };
+        Ok(clone_properties(properties))
+    }
+
+    /// Removes all cached entries.
+    pub fn clear(&self) {
+        self.lock().clear();
+    }
+
+    fn lock(
+        &self,
+    ) -> std::sync::MutexGuard<'_, HashMap<vk::PhysicalDevice, DeviceProperties<'static>>> {
+        lock_ignoring_poison(&self.entries)
+    }
+}
+
+fn clone_properties(properties: &DeviceProperties<'_>) -> DeviceProperties<'static> {
+    DeviceProperties {
+        memory_types: properties.memory_types.to_vec().into(),
+        memory_heaps: properties.memory_heaps.to_vec().into(),
+        max_memory_allocation_count: properties.max_memory_allocation_count,
+        max_memory_allocation_size: properties.max_memory_allocation_size,
+        non_coherent_atom_size: properties.non_coherent_atom_size,
+        buffer_device_address: properties.buffer_device_address,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+
+    use gpu_alloc_types::{MemoryHeap, MemoryPropertyFlags, MemoryType};
+    use vulkanalia::vk::Handle;
+
+    use super::*;
+
+    fn properties(heap_size: u64) -> DeviceProperties<'static> {
+        DeviceProperties {
+            memory_types: vec![MemoryType {
+                props: MemoryPropertyFlags::DEVICE_LOCAL,
+                heap: 0,
+            }]
+            .into(),
+            memory_heaps: vec![MemoryHeap { size: heap_size }].into(),
+            max_memory_allocation_count: 4096,
+            max_memory_allocation_size: u64::MAX,
+            non_coherent_atom_size: 64,
+            buffer_device_address: false,
+        }
+    }
+
+    #[test]
+    fn queries_each_physical_device_once() {
+        let cache = DevicePropertiesCache::new();
+        let queries = Cell::new(0);
+        let query = |physical_device: usize| {
+            let physical_device = vk::PhysicalDevice::from_raw(physical_device);
+            cache
+                .get_or_insert_with(physical_device, || {
+                    queries.set(queries.get() + 1);
+                    Ok::<_, vk::ErrorCode>(properties(queries.get() << 20))
+                })
+                .unwrap()
+                .memory_heaps[0]
+                .size
+        };
+
+        assert_eq!(query(1), 1 << 20);
+        assert_eq!(query(1), 1 << 20);
+        assert_eq!(query(2), 2 << 20);
+        assert_eq!(queries.get(), 2);
+
+        // Clones share the storage
+        let clone = cache.clone();
+        clone.clear();
+        assert_eq!(query(1), 3 << 20);
+        assert_eq!(queries.get(), 3);
+    }
+
+    #[test]
+    fn does_not_cache_failures() {
+        let cache = DevicePropertiesCache::new();
+        let physical_device = vk::PhysicalDevice::from_raw(1);
+
+        let result = cache.get_or_insert_with(physical_device, || {
+            Err(vk::ErrorCode::INITIALIZATION_FAILED)
+        });
+        assert_eq!(result.err(), Some(vk::ErrorCode::INITIALIZATION_FAILED));
+
+        let properties = cache
+            .get_or_insert_with(physical_device, || {
+                Ok::<_, vk::ErrorCode>(properties(1 << 20))
+            })
+            .unwrap();
+        assert_eq!(properties.memory_heaps[0].size, 1 << 20);
+    }
+}
@@ -0,0 +1,547 @@
+use std::ptr::NonNull;
+
+use gpu_alloc_types::{
+    AllocationFlags, DeviceMapError, DeviceProperties, MappedMemoryRange, MemoryDevice, OutOfMemory,
+};
+use vulkanalia::prelude::v1_0::*;
+
+#[cfg(any(feature = "tracing", feature = "log"))]
+use crate::supports_buffer_device_address;
+use crate::{
+    memory_budget, out_of_memory, AllocateExtensions, MemoryBudget, VulkanaliaMemoryDevice,
+    VulkanaliaMemoryError,
+};
+
+/// A wrapper around [`VulkanaliaMemoryDevice`] with additional configuration,
+/// which implements [`MemoryDevice`].
+///
+/// [`VulkanaliaMemoryDevice`] must keep the same layout as `Device`,
+/// so that a `&Device` can be reinterpreted as a memory device.
+/// Therefore any extra state lives in this borrowed wrapper instead.
+///
+/// Created with [`VulkanaliaMemoryDevice::wrap_with_callbacks`] or
+/// [`VulkanaliaMemoryDevice::wrap_with_properties`].
+/// The wrapper is neither `Send` nor `Sync`, since allocation callbacks
+/// are not required to be thread-safe.
+#[derive(Clone, Copy)]
+pub struct ConfiguredMemoryDevice<'a> {
+    device: &'a VulkanaliaMemoryDevice,
+    allocation_callbacks: Option<&'a vk::AllocationCallbacks>,
+    properties: Option<&'a DeviceProperties<'a>>,
+    budget_source: Option<BudgetSource<'a>>,
+}
+
+/// Arguments for [`memory_budget`] queries.
+#[derive(Clone, Copy)]
+struct BudgetSource<'a> {
+    instance: &'a Instance,
+    version: u32,
+    physical_device: vk::PhysicalDevice,
+}
+
+impl VulkanaliaMemoryDevice {
+    /// Wraps the device into a memory device which passes `callbacks`
+    /// to `vkAllocateMemory` and `vkFreeMemory`.
+    ///
+    /// [`wrap`](Self::wrap) does not use any callbacks.
+    pub fn wrap_with_callbacks<'a>(
+        device: &'a Device,
+        callbacks: &'a vk::AllocationCallbacks,
+    ) -> ConfiguredMemoryDevice<'a> {
+        ConfiguredMemoryDevice {
+            device: Self::wrap(device),
+            allocation_callbacks: Some(callbacks),
+            properties: None,
+            budget_source: None,
+        }
+    }
+
+    /// Wraps the device into a memory device which knows properties of its physical device.
+    ///
+    /// Allocations larger than `max_memory_allocation_size` fail before calling the driver.
+    /// `gpu-alloc` never requests such sizes, but manual callers may.
+    /// With `tracing` feature, allocation failures due to memory exhaustion are reported
+    /// along with the index of the exhausted heap.
+    pub fn wrap_with_properties<'a>(
+        device: &'a Device,
+        properties: &'a DeviceProperties<'a>,
+    ) -> ConfiguredMemoryDevice<'a> {
+        ConfiguredMemoryDevice {
+            device: Self::wrap(device),
+            allocation_callbacks: None,
+            properties: Some(properties),
+            budget_source: None,
+        }
+    }
+}
+
+impl<'a> ConfiguredMemoryDevice<'a> {
+    /// Returns the wrapped memory device.
+    pub fn inner(&self) -> &'a VulkanaliaMemoryDevice {
+        self.device
+    }
+
+    /// Returns properties of the physical device, if set.
+    pub fn properties(&self) -> Option<&'a DeviceProperties<'a>> {
+        self.properties
+    }
+
+    /// Uses `properties` of the physical device,
+    /// see [`VulkanaliaMemoryDevice::wrap_with_properties`].
+    pub fn with_properties(self, properties: &'a DeviceProperties<'a>) -> Self {
+        Self {
+            properties: Some(properties),
+            ..self
+        }
+    }
+
+    /// Queries memory budget of the `physical_device` before allocations made with
+    /// [`try_allocate_within_budget`](Self::try_allocate_within_budget).
+    ///
+    /// # Safety
+    ///
+    /// Same as for [`memory_budget`]. The wrapped device must be created
+    /// from the `physical_device`.
+    pub unsafe fn with_budget_source(
+        self,
+        instance: &'a Instance,
+        version: u32,
+        physical_device: vk::PhysicalDevice,
+    ) -> Self {
+        Self {
+            budget_source: Some(BudgetSource {
+                instance,
+                version,
+                physical_device,
+            }),
+            ..self
+        }
+    }
+
+    /// Allocates new memory object like [`try_allocate_memory`](Self::try_allocate_memory) does,
+    /// but fails fast with [`VulkanaliaMemoryError::OverBudget`] when `size` exceeds
+    /// the remaining budget of the memory type's heap.
+    ///
+    /// The budget is re-queried on each call. Its values are advisory and may be stale,
+    /// so an allocation within the budget can still fail and vice versa.
+    /// Without properties, a budget source, extension "VK_EXT_memory_budget"
+    /// or budget information for the heap the check is skipped.
+    ///
+    /// # Safety
+    ///
+    /// Same as for [`MemoryDevice::allocate_memory`].
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(target = "gpu_alloc::vulkanalia", skip(self))
+    )]
+    pub unsafe fn try_allocate_within_budget(
+        &self,
+        size: u64,
+        memory_type: u32,
+        flags: AllocationFlags,
+    ) -> Result<vk::DeviceMemory, VulkanaliaMemoryError> {
+        let budget = match self.budget_source {
+            Some(source) if self.properties.is_some() => {
+                memory_budget(source.instance, source.version, source.physical_device)?
+            }
+            _ => None,
+        };
+
+        allocate_checked(
+            self.properties,
+            budget.as_ref(),
+            size,
+            memory_type,
+            flags,
+            || self.allocate_unchecked(size, memory_type, flags),
+        )
+    }
+
+    /// Allocates new memory object from device,
+    /// returning every Vulkan error to the caller.
+    ///
+    /// When properties are set, sizes above `max_memory_allocation_size` are rejected
+    /// with [`VulkanaliaMemoryError::ExceedsMaxAllocationSize`] before calling the driver,
+    /// which otherwise may fail with a less obvious error or none at all.
+    /// [`MemoryDevice::allocate_memory`] reports them as [`OutOfMemory::OutOfDeviceMemory`].
+    ///
+    /// With `tracing` feature, memory exhaustion is reported with a warning.
+    /// The warning includes the heap index only when properties are set.
+    /// When properties are set, a warning is also emitted if `DEVICE_ADDRESS` is requested
+    /// but they report no `bufferDeviceAddress` support,
+    /// see [`supports_buffer_device_address`](crate::supports_buffer_device_address).
+    ///
+    /// # Safety
+    ///
+    /// Same as for [`MemoryDevice::allocate_memory`].
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(target = "gpu_alloc::vulkanalia", skip(self))
+    )]
+    pub unsafe fn try_allocate_memory(
+        &self,
+        size: u64,
+        memory_type: u32,
+        flags: AllocationFlags,
+    ) -> Result<vk::DeviceMemory, VulkanaliaMemoryError> {
+        allocate_checked(self.properties, None, size, memory_type, flags, || {
+            self.allocate_unchecked(size, memory_type, flags)
+        })
+    }
+
+    unsafe fn allocate_unchecked(
+        &self,
+        size: u64,
+        memory_type: u32,
+        flags: AllocationFlags,
+    ) -> Result<vk::DeviceMemory, VulkanaliaMemoryError> {
+        let ext = AllocateExtensions {
+            allocation_callbacks: self.allocation_callbacks,
+            ..Default::default()
+        };
+        self.device
+            .allocate_memory_with(size, memory_type, flags, &ext)
+    }
+}
+
+/// Checks the allocation against `properties` and `budget` before calling `allocate`,
+/// see [`ConfiguredMemoryDevice::try_allocate_memory`] and
+/// [`ConfiguredMemoryDevice::try_allocate_within_budget`].
+///
+/// Without properties nothing is checked. A memory type without a heap
+/// or a heap without budget information skips the budget check.
+fn allocate_checked<M>(
+    properties: Option<&DeviceProperties<'_>>,
+    budget: Option<&MemoryBudget>,
+    size: u64,
+    memory_type: u32,
+    flags: AllocationFlags,
+    allocate: impl FnOnce() -> Result<M, VulkanaliaMemoryError>,
+) -> Result<M, VulkanaliaMemoryError> {
+    let heap = properties.and_then(|properties| {
+        let memory_type = properties.memory_types.get(memory_type as usize)?;
+        Some(memory_type.heap)
+    });
+
+    if let Some(properties) = properties {
+        if size > properties.max_memory_allocation_size {
+            return Err(VulkanaliaMemoryError::ExceedsMaxAllocationSize);
+        }
+    }
+
+    let remaining = budget
+        .zip(heap)
+        .and_then(|(budget, heap)| budget.remaining(heap as usize));
+    if matches!(remaining, Some(remaining) if size > remaining) {
+        return Err(VulkanaliaMemoryError::OverBudget);
+    }
+
+    #[cfg(any(feature = "tracing", feature = "log"))]
+    if flags.contains(AllocationFlags::DEVICE_ADDRESS) {
+        if let Some(properties) = properties {
+            if !supports_buffer_device_address(properties) {
+                #[cfg(feature = "tracing")]
+                tracing::warn!(
+                    target: "gpu_alloc::vulkanalia",
+                    size,
+                    memory_type,
+                    "DEVICE_ADDRESS requested, but bufferDeviceAddress is likely not enabled"
+                );
+                #[cfg(feature = "log")]
+                log::warn!(
+                    target: "gpu_alloc::vulkanalia",
+                    "DEVICE_ADDRESS requested for {size} bytes from memory type {memory_type}, \
+                     but bufferDeviceAddress is likely not enabled"
+                );
+            }
+        }
+    }
+    #[cfg(not(any(feature = "tracing", feature = "log")))]
+    let _ = flags;
+
+    let result = allocate();
+
+    #[cfg(any(feature = "tracing", feature = "log"))]
+    if let Err(
+        e @ (VulkanaliaMemoryError::OutOfDeviceMemory | VulkanaliaMemoryError::OutOfHostMemory),
+    ) = result
+    {
+        #[cfg(feature = "tracing")]
+        match heap {
+            Some(heap) => tracing::warn!(
+                target: "gpu_alloc::vulkanalia",
+                %e,
+                heap,
+                size,
+                memory_type,
+                "Allocation failed"
+            ),
+            None => tracing::warn!(
+                target: "gpu_alloc::vulkanalia",
+                %e,
+                size,
+                memory_type,
+                "Allocation failed"
+            ),
+        }
+        #[cfg(feature = "log")]
+        match heap {
+            Some(heap) => log::warn!(
+                target: "gpu_alloc::vulkanalia",
+                "Allocation of {size} bytes from memory type {memory_type} \
+                 of heap {heap} failed: {e}"
+            ),
+            None => log::warn!(
+                target: "gpu_alloc::vulkanalia",
+                "Allocation of {size} bytes from memory type {memory_type} failed: {e}"
+            ),
+        }
+    }
+
+    result
+}
+
+impl MemoryDevice<vk::DeviceMemory> for ConfiguredMemoryDevice<'_> {
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(target = "gpu_alloc::vulkanalia", skip(self))
+    )]
+    unsafe fn allocate_memory(
+        &self,
+        size: u64,
+        memory_type: u32,
+        flags: AllocationFlags,
+    ) -> Result<vk::DeviceMemory, OutOfMemory> {
+        self.try_allocate_memory(size, memory_type, flags)
+            .map_err(out_of_memory)
+    }
+
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(target = "gpu_alloc::vulkanalia", skip(self))
+    )]
+    unsafe fn deallocate_memory(&self, memory: vk::DeviceMemory) {
+        self.device
+            .device
+            .free_memory(memory, self.allocation_callbacks);
+    }
+
+    unsafe fn map_memory(
+        &self,
+        memory: &mut vk::DeviceMemory,
+        offset: u64,
+        size: u64,
+    ) -> Result<NonNull<u8>, DeviceMapError> {
+        self.device.map_memory(memory, offset, size)
+    }
+
+    unsafe fn unmap_memory(&self, memory: &mut vk::DeviceMemory) {
+        self.device.unmap_memory(memory);
+    }
+
+    unsafe fn invalidate_memory_ranges(
+        &self,
+        ranges: &[MappedMemoryRange<'_, vk::DeviceMemory>],
+    ) -> Result<(), OutOfMemory> {
+        self.device.invalidate_memory_ranges(ranges)
+    }
+
+    unsafe fn flush_memory_ranges(
+        &self,
+        ranges: &[MappedMemoryRange<'_, vk::DeviceMemory>],
+    ) -> Result<(), OutOfMemory> {
+        self.device.flush_memory_ranges(ranges)
+    }
+}
+
+#[cfg(all(test, feature = "mock"))]
+mod tests {
+    use std::borrow::Cow;
+
+    use gpu_alloc_types::{MemoryHeap, MemoryPropertyFlags, MemoryType};
+
+    use super::*;
+    use crate::MockMemoryDevice;
+
+    fn properties() -> DeviceProperties<'static> {
+        DeviceProperties {
+            memory_types: Cow::Owned(vec![
+                MemoryType {
+                    props: MemoryPropertyFlags::DEVICE_LOCAL,
+                    heap: 0,
+                },
+                // Heap index past `vk::MAX_MEMORY_HEAPS` has no budget information
+                MemoryType {
+                    props: MemoryPropertyFlags::HOST_VISIBLE,
+                    heap: 20,
+                },
+            ]),
+            memory_heaps: Cow::Owned(vec![MemoryHeap { size: 1 << 30 }]),
+            max_memory_allocation_count: 4096,
+            max_memory_allocation_size: 1 << 20,
+            non_coherent_atom_size: 64,
+            buffer_device_address: false,
+        }
+    }
+
+    fn budget(budget: u64, usage: u64) -> MemoryBudget {
+        let mut result = MemoryBudget {
+            budget: [0; vk::MAX_MEMORY_HEAPS],
+            usage: [0; vk::MAX_MEMORY_HEAPS],
+        };
+        result.budget[0] = budget;
+        result.usage[0] = usage;
+        result
+    }
+
+    fn allocate(
+        device: &MockMemoryDevice,
+        properties: Option<&DeviceProperties<'_>>,
+        budget: Option<&MemoryBudget>,
+        size: u64,
+        memory_type: u32,
+        flags: AllocationFlags,
+    ) -> Result<u64, VulkanaliaMemoryError> {
+        allocate_checked(properties, budget, size, memory_type, flags, || unsafe {
+            device
+                .allocate_memory(size, memory_type, flags)
+                .map_err(VulkanaliaMemoryError::from)
+        })
+    }
+
+    #[test]
+    fn rejects_allocations_over_budget() {
+        let device = MockMemoryDevice::new();
+        let props = properties();
+        let budget = budget(1024, 512);
+
+        assert_eq!(
+            allocate(
+                &device,
+                Some(&props),
+                Some(&budget),
+                513,
+                0,
+                AllocationFlags::empty()
+            ),
+            Err(VulkanaliaMemoryError::OverBudget)
+        );
+        assert_eq!(device.calls().allocate, 0);
+
+        let memory = allocate(
+            &device,
+            Some(&props),
+            Some(&budget),
+            512,
+            0,
+            AllocationFlags::empty(),
+        )
+        .unwrap();
+        assert_eq!(device.calls().allocate, 1);
+        unsafe { device.deallocate_memory(memory) };
+    }
+
+    #[test]
+    fn skips_budget_check_without_heap_budget() {
+        let device = MockMemoryDevice::new();
+        let props = properties();
+        let budget = budget(0, 0);
+
+        // Unknown memory type and heap without budget entry
+        for memory_type in [1, 5] {
+            let memory = allocate(
+                &device,
+                Some(&props),
+                Some(&budget),
+                16,
+                memory_type,
+                AllocationFlags::empty(),
+            )
+            .unwrap();
+            unsafe { device.deallocate_memory(memory) };
+        }
+        let memory = allocate(
+            &device,
+            None,
+            Some(&budget),
+            16,
+            0,
+            AllocationFlags::empty(),
+        )
+        .unwrap();
+        unsafe { device.deallocate_memory(memory) };
+
+        assert_eq!(device.calls().allocate, 3);
+    }
+
+    #[test]
+    fn rejects_allocations_over_max_allocation_size() {
+        let device = MockMemoryDevice::new();
+        let props = properties();
+
+        assert_eq!(
+            allocate(
+                &device,
+                Some(&props),
+                None,
+                (1 << 20) + 1,
+                0,
+                AllocationFlags::empty()
+            ),
+            Err(VulkanaliaMemoryError::ExceedsMaxAllocationSize)
+        );
+        assert_eq!(device.calls().allocate, 0);
+
+        // Without properties the driver decides
+        let memory = allocate(
+            &device,
+            None,
+            None,
+            (1 << 20) + 1,
+            0,
+            AllocationFlags::empty(),
+        )
+        .unwrap();
+        assert_eq!(device.calls().allocate, 1);
+        unsafe { device.deallocate_memory(memory) };
+    }
+
+    #[test]
+    fn allocates_device_address_memory_despite_missing_support() {
+        let device = MockMemoryDevice::new();
+        let props = properties();
+        assert!(!crate::supports_buffer_device_address(&props));
+
+        let memory = allocate(
+            &device,
+            Some(&props),
+            None,
+            16,
+            0,
+            AllocationFlags::DEVICE_ADDRESS,
+        )
+        .unwrap();
+        assert_eq!(device.calls().allocate, 1);
+        unsafe { device.deallocate_memory(memory) };
+    }
+
+    #[test]
+    fn reports_allocation_failures() {
+        let device = MockMemoryDevice::new().with_allocation_limit(0);
+        let props = properties();
+
+        assert_eq!(
+            allocate(&device, Some(&props), None, 16, 0, AllocationFlags::empty()),
+            Err(VulkanaliaMemoryError::OutOfDeviceMemory)
+        );
+        assert_eq!(device.calls().allocate, 1);
+    }
+
+    #[test]
+    fn remaining_budget_saturates() {
+        let budget = budget(1024, 2048);
+        assert_eq!(budget.remaining(0), Some(0));
+        assert_eq!(budget.remaining(1), Some(0));
+        assert_eq!(budget.remaining(vk::MAX_MEMORY_HEAPS), None);
+    }
+}
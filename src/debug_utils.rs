@@ -0,0 +1,53 @@
+use std::ffi::CString;
+use std::fmt;
+
+use vulkanalia::prelude::v1_0::*;
+use vulkanalia::vk::{ExtDebugUtilsExtensionInstanceCommands, Handle};
+
+/// Error returned by [`set_memory_name`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SetNameError {
+    /// The name contains a nul byte at the specified position.
+    InteriorNul(usize),
+    /// Error code returned by `vkSetDebugUtilsObjectNameEXT`.
+    Vulkan(vk::ErrorCode),
+}
+
+impl fmt::Display for SetNameError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InteriorNul(position) => {
+                write!(f, "object name contains a nul byte at position {position}")
+            }
+            Self::Vulkan(e) => fmt::Display::fmt(e, f),
+        }
+    }
+}
+
+impl std::error::Error for SetNameError {}
+
+/// Sets a debug name of the memory object, visible in tools like RenderDoc
+/// and in validation layer messages.
+///
+/// # Safety
+///
+/// - Extension "VK_EXT_debug_utils" must be enabled on the `instance`.
+/// - `device` must be created from the `instance`.
+/// - `memory` must have been allocated from the `device`.
+pub unsafe fn set_memory_name(
+    instance: &Instance,
+    device: &Device,
+    memory: vk::DeviceMemory,
+    name: &str,
+) -> Result<(), SetNameError> {
+    let name = CString::new(name).map_err(|e| SetNameError::InteriorNul(e.nul_position()))?;
+
+    let info = vk::DebugUtilsObjectNameInfoEXT::builder()
+        .object_type(vk::ObjectType::DEVICE_MEMORY)
+        .object_handle(memory.as_raw())
+        .object_name(name.as_bytes_with_nul());
+
+    instance
+        .set_debug_utils_object_name_ext(device.handle(), &info)
+        .map_err(SetNameError::Vulkan)
+}
@@ -0,0 +1,134 @@
+use gpu_alloc_types::{AllocationFlags, DeviceProperties};
+use vulkanalia::prelude::v1_0::*;
+use vulkanalia::vk::{DeviceV1_2, KhrBufferDeviceAddressExtensionDeviceCommands};
+
+use crate::{AllocateExtensions, VulkanaliaMemoryDevice, VulkanaliaMemoryError};
+
+/// Returns `true` if `props` report the `bufferDeviceAddress` feature as enabled,
+/// so memory can be allocated with [`AllocationFlags::DEVICE_ADDRESS`].
+///
+/// This only checks the properties, which are as accurate as the query they come from,
+/// e.g. [`device_properties`](crate::device_properties) reports the feature as supported,
+/// not as enabled on the device.
+///
+/// [`AllocationFlags::DEVICE_ADDRESS`]: crate::AllocationFlags::DEVICE_ADDRESS
+pub fn supports_buffer_device_address(props: &DeviceProperties<'_>) -> bool {
+    props.buffer_device_address
+}
+
+/// Returns device address of the `buffer` using Vulkan 1.2 `vkGetBufferDeviceAddress`.
+///
+/// # Safety
+///
+/// - Vulkan 1.2 must be used and the `bufferDeviceAddress` feature must be enabled.
+/// - `buffer` must be created from the `device` with `SHADER_DEVICE_ADDRESS` usage.
+/// - `buffer` must be bound to memory allocated with [`AllocationFlags::DEVICE_ADDRESS`].
+///
+/// [`AllocationFlags::DEVICE_ADDRESS`]: crate::AllocationFlags::DEVICE_ADDRESS
+pub unsafe fn get_buffer_device_address(device: &Device, buffer: vk::Buffer) -> vk::DeviceAddress {
+    let info = vk::BufferDeviceAddressInfo::builder().buffer(buffer);
+    device.get_buffer_device_address(&info)
+}
+
+/// Returns device address of the `buffer`, using `vkGetBufferDeviceAddress`
+/// for Vulkan 1.2 and above and `vkGetBufferDeviceAddressKHR` otherwise.
+///
+/// # Safety
+///
+/// - `version` must not be higher than the `api_version` of the `device`'s instance.
+/// - For Vulkan prior 1.2 extension "VK_KHR_buffer_device_address" must be enabled.
+/// - The `bufferDeviceAddress` feature must be enabled.
+/// - `buffer` must be created from the `device` with `SHADER_DEVICE_ADDRESS` usage.
+/// - `buffer` must be bound to memory allocated with [`AllocationFlags::DEVICE_ADDRESS`].
+///
+/// [`AllocationFlags::DEVICE_ADDRESS`]: crate::AllocationFlags::DEVICE_ADDRESS
+pub unsafe fn get_buffer_device_address_for_version(
+    device: &Device,
+    version: u32,
+    buffer: vk::Buffer,
+) -> vk::DeviceAddress {
+    let info = vk::BufferDeviceAddressInfo::builder().buffer(buffer);
+    if vk::version_minor(version) >= 2 {
+        device.get_buffer_device_address(&info)
+    } else {
+        device.get_buffer_device_address_khr(&info)
+    }
+}
+
+impl VulkanaliaMemoryDevice {
+    /// Allocates new memory object at the opaque address recorded during capture.
+    ///
+    /// When `opaque_capture_address` is nonzero, `VkMemoryOpaqueCaptureAddressAllocateInfo`
+    /// is chained into the allocate info and both `DEVICE_ADDRESS` and
+    /// `DEVICE_ADDRESS_CAPTURE_REPLAY` allocate flags are set.
+    /// With zero address this is the same as a regular allocation.
+    /// Returns [`VulkanaliaMemoryError::InvalidOpaqueCaptureAddress`] when the address
+    /// cannot be reused, e.g. because it is already taken in the replay session.
+    ///
+    /// # Safety
+    ///
+    /// Same as for [`MemoryDevice::allocate_memory`](gpu_alloc_types::MemoryDevice::allocate_memory).
+    /// When `opaque_capture_address` is nonzero, the `bufferDeviceAddressCaptureReplay`
+    /// feature must be enabled and the address must have been retrieved with
+    /// [`get_device_memory_opaque_capture_address`](Self::get_device_memory_opaque_capture_address)
+    /// for an identically created allocation.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(target = "gpu_alloc::vulkanalia", skip(self))
+    )]
+    pub unsafe fn allocate_memory_with_capture_address(
+        &self,
+        size: u64,
+        memory_type: u32,
+        flags: AllocationFlags,
+        opaque_capture_address: u64,
+    ) -> Result<vk::DeviceMemory, VulkanaliaMemoryError> {
+        let ext = AllocateExtensions {
+            opaque_capture_address,
+            ..Default::default()
+        };
+        self.allocate_memory_with(size, memory_type, flags, &ext)
+    }
+
+    /// Allocates new memory object whose address can be captured for replay.
+    ///
+    /// Sets both `DEVICE_ADDRESS` and `DEVICE_ADDRESS_CAPTURE_REPLAY` allocate flags,
+    /// regardless of `flags`. Retrieve the address with
+    /// [`get_device_memory_opaque_capture_address`](Self::get_device_memory_opaque_capture_address).
+    ///
+    /// # Safety
+    ///
+    /// Same as for [`MemoryDevice::allocate_memory`](gpu_alloc_types::MemoryDevice::allocate_memory).
+    /// Vulkan 1.2 must be used and the `bufferDeviceAddressCaptureReplay` feature must be enabled.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(target = "gpu_alloc::vulkanalia", skip(self))
+    )]
+    pub unsafe fn allocate_memory_capture_replay(
+        &self,
+        size: u64,
+        memory_type: u32,
+        flags: AllocationFlags,
+    ) -> Result<vk::DeviceMemory, VulkanaliaMemoryError> {
+        let ext = AllocateExtensions {
+            capture_replay: true,
+            ..Default::default()
+        };
+        self.allocate_memory_with(size, memory_type, flags, &ext)
+    }
+
+    /// Returns opaque capture address of the memory object,
+    /// to be passed to [`allocate_memory_with_capture_address`] on replay.
+    ///
+    /// # Safety
+    ///
+    /// - Vulkan 1.2 must be used and the `bufferDeviceAddressCaptureReplay` feature must be enabled.
+    /// - `memory` must have been allocated from this device with
+    ///   `DEVICE_ADDRESS` allocate flag.
+    ///
+    /// [`allocate_memory_with_capture_address`]: Self::allocate_memory_with_capture_address
+    pub unsafe fn get_device_memory_opaque_capture_address(&self, memory: vk::DeviceMemory) -> u64 {
+        let info = vk::DeviceMemoryOpaqueCaptureAddressInfo::builder().memory(memory);
+        self.device.get_device_memory_opaque_capture_address(&info)
+    }
+}
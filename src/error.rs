@@ -0,0 +1,399 @@
+use std::fmt;
+
+use gpu_alloc_types::{DeviceMapError, OutOfMemory};
+use vulkanalia::vk;
+
+/// Error returned by the non-panicking memory device methods.
+///
+/// Unlike [`OutOfMemory`] and [`DeviceMapError`] it keeps the exact
+/// [`vk::ErrorCode`] of unexpected failures, so it can be logged before being
+/// collapsed into `gpu-alloc-types` errors.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum VulkanaliaMemoryError {
+    /// Device memory exhausted.
+    ///
+    /// Returned by `vkAllocateMemory`, `vkMapMemory`,
+    /// `vkFlushMappedMemoryRanges` and `vkInvalidateMappedMemoryRanges`.
+    OutOfDeviceMemory,
+    /// Host memory exhausted.
+    ///
+    /// Returned by `vkAllocateMemory`, `vkMapMemory`,
+    /// `vkFlushMappedMemoryRanges` and `vkInvalidateMappedMemoryRanges`.
+    OutOfHostMemory,
+    /// Map failed due to implementation specific error.
+    ///
+    /// Returned by `vkMapMemory`.
+    MapFailed,
+    /// External handle is not a valid handle of the specified type.
+    ///
+    /// Returned by `vkAllocateMemory` when importing external memory.
+    InvalidExternalHandle,
+    /// Opaque capture address is not available for reuse.
+    ///
+    /// Returned by `vkAllocateMemory` when replaying with an opaque capture address.
+    InvalidOpaqueCaptureAddress,
+    /// Memory type is not host visible, so it cannot be accessed by the host.
+    ///
+    /// Returned before calling Vulkan when an operation requires mapping the memory.
+    NotHostVisible,
+    /// Memory type is not protected.
+    ///
+    /// Returned before calling Vulkan when protected memory is requested.
+    NotProtected,
+    /// Requested size exceeds the remaining memory budget of the heap.
+    ///
+    /// Returned before calling `vkAllocateMemory` by budget-aware allocations.
+    OverBudget,
+    /// Requested size exceeds `maxMemoryAllocationSize` of the device.
+    ///
+    /// Returned before calling `vkAllocateMemory` by allocations aware of device properties.
+    ExceedsMaxAllocationSize,
+    /// Memory type is not allowed by memory requirements of the resource.
+    ///
+    /// Returned before calling `vkAllocateMemory` by helpers binding resources.
+    IncompatibleMemoryType,
+    /// Accessed range does not fit into the mapped region.
+    ///
+    /// Returned before accessing memory by bounds-checked helpers.
+    OutOfBounds,
+    /// Any other error code returned by the driver.
+    Other(vk::ErrorCode),
+}
+
+impl From<vk::ErrorCode> for VulkanaliaMemoryError {
+    fn from(e: vk::ErrorCode) -> Self {
+        match e {
+            vk::ErrorCode::OUT_OF_DEVICE_MEMORY => Self::OutOfDeviceMemory,
+            vk::ErrorCode::OUT_OF_HOST_MEMORY => Self::OutOfHostMemory,
+            vk::ErrorCode::MEMORY_MAP_FAILED => Self::MapFailed,
+            vk::ErrorCode::INVALID_EXTERNAL_HANDLE => Self::InvalidExternalHandle,
+            vk::ErrorCode::INVALID_OPAQUE_CAPTURE_ADDRESS => Self::InvalidOpaqueCaptureAddress,
+            e => Self::Other(e),
+        }
+    }
+}
+
+impl From<OutOfMemory> for VulkanaliaMemoryError {
+    fn from(e: OutOfMemory) -> Self {
+        match e {
+            OutOfMemory::OutOfDeviceMemory => Self::OutOfDeviceMemory,
+            OutOfMemory::OutOfHostMemory => Self::OutOfHostMemory,
+        }
+    }
+}
+
+impl From<DeviceMapError> for VulkanaliaMemoryError {
+    fn from(e: DeviceMapError) -> Self {
+        match e {
+            DeviceMapError::OutOfDeviceMemory => Self::OutOfDeviceMemory,
+            DeviceMapError::OutOfHostMemory => Self::OutOfHostMemory,
+            DeviceMapError::MapFailed => Self::MapFailed,
+        }
+    }
+}
+
+/// Lossy conversion: variants other than memory exhaustion become
+/// [`OutOfMemory::OutOfDeviceMemory`], except for
+/// [`InvalidOpaqueCaptureAddress`](VulkanaliaMemoryError::InvalidOpaqueCaptureAddress)
+/// which becomes [`OutOfMemory::OutOfHostMemory`], as reported by [`MemoryDevice::allocate_memory`].
+///
+/// [`MemoryDevice::allocate_memory`]: gpu_alloc_types::MemoryDevice::allocate_memory
+impl From<VulkanaliaMemoryError> for OutOfMemory {
+    fn from(e: VulkanaliaMemoryError) -> Self {
+        match e {
+            VulkanaliaMemoryError::OutOfHostMemory
+            | VulkanaliaMemoryError::InvalidOpaqueCaptureAddress => OutOfMemory::OutOfHostMemory,
+            VulkanaliaMemoryError::OutOfDeviceMemory
+            | VulkanaliaMemoryError::MapFailed
+            | VulkanaliaMemoryError::InvalidExternalHandle
+            | VulkanaliaMemoryError::NotHostVisible
+            | VulkanaliaMemoryError::NotProtected
+            | VulkanaliaMemoryError::OverBudget
+            | VulkanaliaMemoryError::ExceedsMaxAllocationSize
+            | VulkanaliaMemoryError::IncompatibleMemoryType
+            | VulkanaliaMemoryError::OutOfBounds
+            | VulkanaliaMemoryError::Other(_) => OutOfMemory::OutOfDeviceMemory,
+        }
+    }
+}
+
+/// Lossy conversion: variants other than memory exhaustion become
+/// [`DeviceMapError::MapFailed`].
+impl From<VulkanaliaMemoryError> for DeviceMapError {
+    fn from(e: VulkanaliaMemoryError) -> Self {
+        match e {
+            VulkanaliaMemoryError::OutOfDeviceMemory => DeviceMapError::OutOfDeviceMemory,
+            VulkanaliaMemoryError::OutOfHostMemory => DeviceMapError::OutOfHostMemory,
+            VulkanaliaMemoryError::MapFailed
+            | VulkanaliaMemoryError::InvalidExternalHandle
+            | VulkanaliaMemoryError::InvalidOpaqueCaptureAddress
+            | VulkanaliaMemoryError::NotHostVisible
+            | VulkanaliaMemoryError::NotProtected
+            | VulkanaliaMemoryError::OverBudget
+            | VulkanaliaMemoryError::ExceedsMaxAllocationSize
+            | VulkanaliaMemoryError::IncompatibleMemoryType
+            | VulkanaliaMemoryError::OutOfBounds
+            | VulkanaliaMemoryError::Other(_) => DeviceMapError::MapFailed,
+        }
+    }
+}
+
+/// Converts [`OutOfMemory`] back into the Vulkan error code it originates from.
+///
+/// Lossy, since other Vulkan errors are converted into [`OutOfMemory::OutOfDeviceMemory`].
+pub fn out_of_memory_to_error_code(e: OutOfMemory) -> vk::ErrorCode {
+    match e {
+        OutOfMemory::OutOfDeviceMemory => vk::ErrorCode::OUT_OF_DEVICE_MEMORY,
+        OutOfMemory::OutOfHostMemory => vk::ErrorCode::OUT_OF_HOST_MEMORY,
+    }
+}
+
+/// Converts [`DeviceMapError`] back into the Vulkan error code it originates from.
+///
+/// Lossy, since other Vulkan errors are converted into [`DeviceMapError::MapFailed`],
+/// which maps to `MEMORY_MAP_FAILED`.
+pub fn device_map_error_to_error_code(e: DeviceMapError) -> vk::ErrorCode {
+    match e {
+        DeviceMapError::OutOfDeviceMemory => vk::ErrorCode::OUT_OF_DEVICE_MEMORY,
+        DeviceMapError::OutOfHostMemory => vk::ErrorCode::OUT_OF_HOST_MEMORY,
+        DeviceMapError::MapFailed => vk::ErrorCode::MEMORY_MAP_FAILED,
+    }
+}
+
+/// Returns a short description of the error, independent of its `Debug` output.
+pub fn describe_out_of_memory(e: &OutOfMemory) -> &'static str {
+    match e {
+        OutOfMemory::OutOfDeviceMemory => "out of device memory",
+        OutOfMemory::OutOfHostMemory => "out of host memory",
+    }
+}
+
+/// Returns a short description of the error, independent of its `Debug` output.
+pub fn describe_map_error(e: &DeviceMapError) -> &'static str {
+    match e {
+        DeviceMapError::OutOfDeviceMemory => "out of device memory",
+        DeviceMapError::OutOfHostMemory => "out of host memory",
+        DeviceMapError::MapFailed => "memory map failed",
+    }
+}
+
+impl fmt::Display for VulkanaliaMemoryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::OutOfDeviceMemory => f.write_str("out of device memory"),
+            Self::OutOfHostMemory => f.write_str("out of host memory"),
+            Self::MapFailed => f.write_str("memory map failed"),
+            Self::InvalidExternalHandle => f.write_str("invalid external handle"),
+            Self::InvalidOpaqueCaptureAddress => f.write_str("invalid opaque capture address"),
+            Self::NotHostVisible => f.write_str("memory type is not host visible"),
+            Self::NotProtected => f.write_str("memory type is not protected"),
+            Self::OverBudget => f.write_str("memory budget exceeded"),
+            Self::ExceedsMaxAllocationSize => {
+                f.write_str("maximum memory allocation size exceeded")
+            }
+            Self::IncompatibleMemoryType => {
+                f.write_str("memory type is not allowed by memory requirements")
+            }
+            Self::OutOfBounds => f.write_str("access out of bounds of the mapped region"),
+            Self::Other(e) => fmt::Display::fmt(e, f),
+        }
+    }
+}
+
+impl std::error::Error for VulkanaliaMemoryError {}
+
+/// Error returned by [`try_device_properties`](crate::try_device_properties).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum DevicePropertiesError {
+    /// Requested `version` is higher than the instance version.
+    VersionTooHigh {
+        /// Requested Vulkan version.
+        requested: u32,
+        /// Vulkan version the instance was created with.
+        instance: u32,
+    },
+    /// Error code returned by the driver while querying properties.
+    Vulkan(vk::ErrorCode),
+}
+
+impl From<vk::ErrorCode> for DevicePropertiesError {
+    fn from(e: vk::ErrorCode) -> Self {
+        Self::Vulkan(e)
+    }
+}
+
+impl fmt::Display for DevicePropertiesError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::VersionTooHigh {
+                requested,
+                instance,
+            } => write!(
+                f,
+                "requested Vulkan {}.{} is higher than instance Vulkan {}.{}",
+                vk::version_major(*requested),
+                vk::version_minor(*requested),
+                vk::version_major(*instance),
+                vk::version_minor(*instance),
+            ),
+            Self::Vulkan(e) => fmt::Display::fmt(e, f),
+        }
+    }
+}
+
+impl std::error::Error for DevicePropertiesError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn error_code_conversion() {
+        for (code, expected) in [
+            (
+                vk::ErrorCode::OUT_OF_DEVICE_MEMORY,
+                VulkanaliaMemoryError::OutOfDeviceMemory,
+            ),
+            (
+                vk::ErrorCode::OUT_OF_HOST_MEMORY,
+                VulkanaliaMemoryError::OutOfHostMemory,
+            ),
+            (
+                vk::ErrorCode::MEMORY_MAP_FAILED,
+                VulkanaliaMemoryError::MapFailed,
+            ),
+            (
+                vk::ErrorCode::INVALID_EXTERNAL_HANDLE,
+                VulkanaliaMemoryError::InvalidExternalHandle,
+            ),
+            (
+                vk::ErrorCode::INVALID_OPAQUE_CAPTURE_ADDRESS,
+                VulkanaliaMemoryError::InvalidOpaqueCaptureAddress,
+            ),
+            (
+                vk::ErrorCode::DEVICE_LOST,
+                VulkanaliaMemoryError::Other(vk::ErrorCode::DEVICE_LOST),
+            ),
+        ] {
+            assert_eq!(VulkanaliaMemoryError::from(code), expected);
+        }
+    }
+
+    #[test]
+    fn lossy_conversions_keep_memory_exhaustion() {
+        assert!(matches!(
+            VulkanaliaMemoryError::OutOfHostMemory.into(),
+            OutOfMemory::OutOfHostMemory
+        ));
+        assert!(matches!(
+            VulkanaliaMemoryError::OutOfDeviceMemory.into(),
+            OutOfMemory::OutOfDeviceMemory
+        ));
+        assert!(matches!(
+            VulkanaliaMemoryError::OutOfHostMemory.into(),
+            DeviceMapError::OutOfHostMemory
+        ));
+        assert!(matches!(
+            VulkanaliaMemoryError::OutOfDeviceMemory.into(),
+            DeviceMapError::OutOfDeviceMemory
+        ));
+    }
+
+    #[test]
+    fn lossy_conversions_report_missing_capture_address_as_host_memory() {
+        let e = VulkanaliaMemoryError::InvalidOpaqueCaptureAddress;
+        assert!(matches!(e.into(), OutOfMemory::OutOfHostMemory));
+        assert!(matches!(e.into(), DeviceMapError::MapFailed));
+    }
+
+    #[test]
+    fn lossy_conversions_collapse_other_errors() {
+        for e in [
+            VulkanaliaMemoryError::MapFailed,
+            VulkanaliaMemoryError::InvalidExternalHandle,
+            VulkanaliaMemoryError::NotHostVisible,
+            VulkanaliaMemoryError::NotProtected,
+            VulkanaliaMemoryError::OverBudget,
+            VulkanaliaMemoryError::ExceedsMaxAllocationSize,
+            VulkanaliaMemoryError::IncompatibleMemoryType,
+            VulkanaliaMemoryError::OutOfBounds,
+            VulkanaliaMemoryError::Other(vk::ErrorCode::DEVICE_LOST),
+        ] {
+            assert!(matches!(e.into(), OutOfMemory::OutOfDeviceMemory));
+            assert!(matches!(e.into(), DeviceMapError::MapFailed));
+        }
+    }
+
+    #[test]
+    fn gpu_alloc_errors_convert_losslessly() {
+        assert_eq!(
+            VulkanaliaMemoryError::from(OutOfMemory::OutOfDeviceMemory),
+            VulkanaliaMemoryError::OutOfDeviceMemory
+        );
+        assert_eq!(
+            VulkanaliaMemoryError::from(OutOfMemory::OutOfHostMemory),
+            VulkanaliaMemoryError::OutOfHostMemory
+        );
+        assert_eq!(
+            VulkanaliaMemoryError::from(DeviceMapError::OutOfDeviceMemory),
+            VulkanaliaMemoryError::OutOfDeviceMemory
+        );
+        assert_eq!(
+            VulkanaliaMemoryError::from(DeviceMapError::OutOfHostMemory),
+            VulkanaliaMemoryError::OutOfHostMemory
+        );
+        assert_eq!(
+            VulkanaliaMemoryError::from(DeviceMapError::MapFailed),
+            VulkanaliaMemoryError::MapFailed
+        );
+    }
+
+    #[test]
+    fn error_codes_round_trip() {
+        for code in [
+            vk::ErrorCode::OUT_OF_DEVICE_MEMORY,
+            vk::ErrorCode::OUT_OF_HOST_MEMORY,
+        ] {
+            let e = OutOfMemory::from(VulkanaliaMemoryError::from(code));
+            assert_eq!(out_of_memory_to_error_code(e), code);
+        }
+        for code in [
+            vk::ErrorCode::OUT_OF_DEVICE_MEMORY,
+            vk::ErrorCode::OUT_OF_HOST_MEMORY,
+            vk::ErrorCode::MEMORY_MAP_FAILED,
+        ] {
+            let e = DeviceMapError::from(VulkanaliaMemoryError::from(code));
+            assert_eq!(device_map_error_to_error_code(e), code);
+        }
+    }
+
+    #[test]
+    fn out_of_memory_descriptions() {
+        assert_eq!(
+            describe_out_of_memory(&OutOfMemory::OutOfDeviceMemory),
+            "out of device memory"
+        );
+        assert_eq!(
+            describe_out_of_memory(&OutOfMemory::OutOfHostMemory),
+            "out of host memory"
+        );
+    }
+
+    #[test]
+    fn map_error_descriptions() {
+        assert_eq!(
+            describe_map_error(&DeviceMapError::OutOfDeviceMemory),
+            "out of device memory"
+        );
+        assert_eq!(
+            describe_map_error(&DeviceMapError::OutOfHostMemory),
+            "out of host memory"
+        );
+        assert_eq!(
+            describe_map_error(&DeviceMapError::MapFailed),
+            "memory map failed"
+        );
+    }
+}
@@ -0,0 +1,343 @@
+#[cfg(unix)]
+use std::os::unix::io::RawFd;
+use std::ptr::NonNull;
+
+use gpu_alloc_types::AllocationFlags;
+use vulkanalia::prelude::v1_0::*;
+use vulkanalia::vk::ExtExternalMemoryHostExtensionDeviceCommands;
+#[cfg(unix)]
+use vulkanalia::vk::KhrExternalMemoryFdExtensionDeviceCommands;
+
+use crate::{AllocateExtensions, VulkanaliaMemoryDevice, VulkanaliaMemoryError};
+
+/// Returns structures chained by [`VulkanaliaMemoryDevice::import_memory_fd`].
+#[cfg(unix)]
+fn import_fd_extensions(
+    handle_type: vk::ExternalMemoryHandleTypeFlags,
+    fd: RawFd,
+) -> AllocateExtensions<'static> {
+    AllocateExtensions {
+        import_fd: Some((handle_type, fd)),
+        ..Default::default()
+    }
+}
+
+/// Runs the properties `query` and returns the compatible memory type bits.
+#[cfg(unix)]
+fn memory_type_bits_with(
+    query: impl FnOnce(&mut vk::MemoryFdPropertiesKHR) -> VkResult<()>,
+) -> Result<u32, VulkanaliaMemoryError> {
+    let mut properties = vk::MemoryFdPropertiesKHR::default();
+    query(&mut properties).map_err(VulkanaliaMemoryError::from)?;
+    Ok(properties.memory_type_bits)
+}
+
+impl VulkanaliaMemoryDevice {
+    /// Allocates new memory object which can be exported as an external handle.
+    ///
+    /// Chains `VkExportMemoryAllocateInfo` with the specified `handle_types`
+    /// into the allocate info.
+    ///
+    /// # Safety
+    ///
+    /// Same as for [`MemoryDevice::allocate_memory`](gpu_alloc_types::MemoryDevice::allocate_memory).
+    /// In addition:
+    /// - Vulkan 1.1 or extension "VK_KHR_external_memory" must be enabled,
+    ///   along with the extension for each of `handle_types`
+    ///   (e.g. "VK_KHR_external_memory_fd" for `OPAQUE_FD`).
+    /// - `handle_types` must be supported for export with the memory type.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(target = "gpu_alloc::vulkanalia", skip(self))
+    )]
+    pub unsafe fn allocate_exportable_memory(
+        &self,
+        size: u64,
+        memory_type: u32,
+        flags: AllocationFlags,
+        handle_types: vk::ExternalMemoryHandleTypeFlags,
+    ) -> Result<vk::DeviceMemory, VulkanaliaMemoryError> {
+        let ext = AllocateExtensions {
+            export: Some(handle_types),
+            ..Default::default()
+        };
+        self.allocate_memory_with(size, memory_type, flags, &ext)
+    }
+
+    /// Exports a POSIX file descriptor referencing the memory object.
+    ///
+    /// A new file descriptor is created on each call and is owned by the caller.
+    ///
+    /// # Safety
+    ///
+    /// - Extension "VK_KHR_external_memory_fd" must be enabled.
+    /// - `memory` must have been allocated from this device with
+    ///   `handle_type` included in its export handle types
+    ///   (see [`allocate_exportable_memory`](Self::allocate_exportable_memory)).
+    /// - `handle_type` must be either `OPAQUE_FD` or `DMA_BUF_EXT`.
+    #[cfg(unix)]
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(target = "gpu_alloc::vulkanalia", skip(self))
+    )]
+    pub unsafe fn get_memory_fd(
+        &self,
+        memory: vk::DeviceMemory,
+        handle_type: vk::ExternalMemoryHandleTypeFlags,
+    ) -> Result<RawFd, VulkanaliaMemoryError> {
+        let info = vk::MemoryGetFdInfoKHR::builder()
+            .memory(memory)
+            .handle_type(handle_type);
+
+        self.device
+            .get_memory_fd_khr(&info)
+            .map_err(VulkanaliaMemoryError::from)
+    }
+
+    /// Imports memory from a POSIX file descriptor.
+    ///
+    /// Chains `VkImportMemoryFdInfoKHR` into the allocate info.
+    ///
+    /// On success the driver takes ownership of `fd` and the application must not
+    /// use it anymore. On failure `fd` is left untouched and remains owned
+    /// by the caller. Invalid descriptors are reported as
+    /// [`VulkanaliaMemoryError::InvalidExternalHandle`].
+    ///
+    /// # Safety
+    ///
+    /// - Extension "VK_KHR_external_memory_fd" must be enabled.
+    /// - `memory_type` must be valid index for memory type associated with this device
+    ///   and be compatible with the imported memory.
+    /// - `size` must match the size of the exported memory for `OPAQUE_FD` handles.
+    /// - `handle_type` must be either `OPAQUE_FD` or `DMA_BUF_EXT`.
+    #[cfg(unix)]
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(target = "gpu_alloc::vulkanalia", skip(self))
+    )]
+    pub unsafe fn import_memory_fd(
+        &self,
+        size: u64,
+        memory_type: u32,
+        fd: RawFd,
+        handle_type: vk::ExternalMemoryHandleTypeFlags,
+    ) -> Result<vk::DeviceMemory, VulkanaliaMemoryError> {
+        let ext = import_fd_extensions(handle_type, fd);
+        self.allocate_memory_with(size, memory_type, AllocationFlags::empty(), &ext)
+    }
+
+    /// Returns a bitmask of memory types the file descriptor can be imported as,
+    /// the same encoding as `VkMemoryRequirements::memoryTypeBits`.
+    ///
+    /// Invalid descriptors are reported as [`VulkanaliaMemoryError::InvalidExternalHandle`].
+    ///
+    /// # Safety
+    ///
+    /// - Extension "VK_KHR_external_memory_fd" must be enabled,
+    ///   along with "VK_EXT_external_memory_dma_buf" for `DMA_BUF_EXT` handles.
+    /// - `handle_type` must not be `OPAQUE_FD`, whose memory types are only known
+    ///   from the exporting side.
+    #[cfg(unix)]
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(target = "gpu_alloc::vulkanalia", skip(self))
+    )]
+    pub unsafe fn get_memory_fd_properties(
+        &self,
+        handle_type: vk::ExternalMemoryHandleTypeFlags,
+        fd: RawFd,
+    ) -> Result<u32, VulkanaliaMemoryError> {
+        memory_type_bits_with(|properties| {
+            self.device
+                .get_memory_fd_properties_khr(handle_type, fd, properties)
+        })
+    }
+
+    /// Imports memory from a DMA-BUF file descriptor, e.g. one shared through GBM or Wayland.
+    ///
+    /// Same as [`import_memory_fd`](Self::import_memory_fd) with `DMA_BUF_EXT` handle type.
+    /// Use [`get_memory_fd_properties`](Self::get_memory_fd_properties) to find memory types
+    /// compatible with the descriptor.
+    ///
+    /// # Safety
+    ///
+    /// - Extensions "VK_KHR_external_memory_fd" and "VK_EXT_external_memory_dma_buf"
+    ///   must be enabled.
+    /// - `memory_type` must be one of the memory types compatible with `fd`.
+    /// - `size` must not exceed the size of the DMA-BUF.
+    #[cfg(unix)]
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(target = "gpu_alloc::vulkanalia", skip(self))
+    )]
+    pub unsafe fn import_memory_dma_buf(
+        &self,
+        size: u64,
+        memory_type: u32,
+        fd: RawFd,
+    ) -> Result<vk::DeviceMemory, VulkanaliaMemoryError> {
+        self.import_memory_fd(
+            size,
+            memory_type,
+            fd,
+            vk::ExternalMemoryHandleTypeFlags::DMA_BUF_EXT,
+        )
+    }
+
+    /// Exports a DMA-BUF file descriptor referencing the memory object.
+    ///
+    /// Same as [`get_memory_fd`](Self::get_memory_fd) with `DMA_BUF_EXT` handle type.
+    ///
+    /// # Safety
+    ///
+    /// - Extensions "VK_KHR_external_memory_fd" and "VK_EXT_external_memory_dma_buf"
+    ///   must be enabled.
+    /// - `memory` must have been allocated from this device with `DMA_BUF_EXT`
+    ///   included in its export handle types.
+    #[cfg(unix)]
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(target = "gpu_alloc::vulkanalia", skip(self))
+    )]
+    pub unsafe fn export_memory_dma_buf(
+        &self,
+        memory: vk::DeviceMemory,
+    ) -> Result<RawFd, VulkanaliaMemoryError> {
+        self.get_memory_fd(memory, vk::ExternalMemoryHandleTypeFlags::DMA_BUF_EXT)
+    }
+
+    /// Imports host memory, e.g. a region mapped with `mmap`, as a memory object.
+    ///
+    /// Chains `VkImportMemoryHostPointerInfoEXT` into the allocate info.
+    /// Use [`get_memory_host_pointer_properties`](Self::get_memory_host_pointer_properties)
+    /// to find memory types compatible with the pointer.
+    ///
+    /// # Safety
+    ///
+    /// - Extension "VK_EXT_external_memory_host" must be enabled.
+    /// - `host_pointer` and `size` must be multiples of
+    ///   `PhysicalDeviceExternalMemoryHostPropertiesEXT::min_imported_host_pointer_alignment`.
+    /// - `handle_type` must be either `HOST_ALLOCATION_EXT` or `HOST_MAPPED_FOREIGN_MEMORY_EXT`.
+    /// - `memory_type` must be one of the memory types compatible with `host_pointer`.
+    /// - Host memory must stay valid and not be imported again until the memory object is freed.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(target = "gpu_alloc::vulkanalia", skip(self))
+    )]
+    pub unsafe fn import_host_pointer_memory(
+        &self,
+        size: u64,
+        memory_type: u32,
+        host_pointer: NonNull<u8>,
+        handle_type: vk::ExternalMemoryHandleTypeFlags,
+    ) -> Result<vk::DeviceMemory, VulkanaliaMemoryError> {
+        let ext = AllocateExtensions {
+            import_host_pointer: Some((handle_type, host_pointer)),
+            ..Default::default()
+        };
+        self.allocate_memory_with(size, memory_type, AllocationFlags::empty(), &ext)
+    }
+
+    /// Returns a bitmask of memory types host memory at `host_pointer` can be imported as,
+    /// the same encoding as `VkMemoryRequirements::memoryTypeBits`.
+    ///
+    /// Invalid pointers are reported as [`VulkanaliaMemoryError::InvalidExternalHandle`].
+    ///
+    /// # Safety
+    ///
+    /// - Extension "VK_EXT_external_memory_host" must be enabled.
+    /// - `host_pointer` must be a multiple of
+    ///   `PhysicalDeviceExternalMemoryHostPropertiesEXT::min_imported_host_pointer_alignment`.
+    /// - `handle_type` must be either `HOST_ALLOCATION_EXT` or `HOST_MAPPED_FOREIGN_MEMORY_EXT`.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(target = "gpu_alloc::vulkanalia", skip(self))
+    )]
+    pub unsafe fn get_memory_host_pointer_properties(
+        &self,
+        handle_type: vk::ExternalMemoryHandleTypeFlags,
+        host_pointer: NonNull<u8>,
+    ) -> Result<u32, VulkanaliaMemoryError> {
+        let mut properties = vk::MemoryHostPointerPropertiesEXT::default();
+
+        self.device
+            .get_memory_host_pointer_properties_ext(
+                handle_type,
+                host_pointer.as_ptr().cast(),
+                &mut properties,
+            )
+            .map_err(VulkanaliaMemoryError::from)?;
+
+        Ok(properties.memory_type_bits)
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use crate::allocate::{chain_types, find_in_chain, with_allocate_info};
+
+    #[test]
+    fn import_fd_chains_import_info() {
+        let ext = import_fd_extensions(vk::ExternalMemoryHandleTypeFlags::OPAQUE_FD, 7);
+
+        let (info, types, import) =
+            with_allocate_info(4096, 3, AllocationFlags::empty(), &ext, |info| {
+                (
+                    (info.allocation_size, info.memory_type_index),
+                    chain_types(info),
+                    find_in_chain::<vk::ImportMemoryFdInfoKHR>(info),
+                )
+            });
+
+        assert_eq!(info, (4096, 3));
+        assert_eq!(types, [vk::StructureType::IMPORT_MEMORY_FD_INFO_KHR]);
+        let import = import.unwrap();
+        assert_eq!(
+            import.handle_type,
+            vk::ExternalMemoryHandleTypeFlags::OPAQUE_FD
+        );
+        assert_eq!(import.fd, 7);
+    }
+
+    #[test]
+    fn dma_buf_import_chains_dma_buf_handle_type() {
+        let ext = import_fd_extensions(vk::ExternalMemoryHandleTypeFlags::DMA_BUF_EXT, 11);
+
+        let (types, import) =
+            with_allocate_info(65536, 0, AllocationFlags::empty(), &ext, |info| {
+                (
+                    chain_types(info),
+                    find_in_chain::<vk::ImportMemoryFdInfoKHR>(info),
+                )
+            });
+
+        assert_eq!(types, [vk::StructureType::IMPORT_MEMORY_FD_INFO_KHR]);
+        let import = import.unwrap();
+        assert_eq!(
+            import.handle_type,
+            vk::ExternalMemoryHandleTypeFlags::DMA_BUF_EXT
+        );
+        assert_eq!(import.fd, 11);
+    }
+
+    #[test]
+    fn memory_type_bits_are_read_from_properties() {
+        let bits = memory_type_bits_with(|properties| {
+            properties.memory_type_bits = 0b1010;
+            Ok(())
+        });
+
+        assert_eq!(bits.unwrap(), 0b1010);
+    }
+
+    #[test]
+    fn memory_type_bits_query_maps_invalid_handle() {
+        let bits = memory_type_bits_with(|_| Err(vk::ErrorCode::INVALID_EXTERNAL_HANDLE));
+
+        assert!(matches!(
+            bits,
+            Err(VulkanaliaMemoryError::InvalidExternalHandle)
+        ));
+    }
+}
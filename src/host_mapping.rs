@@ -0,0 +1,453 @@
+use std::mem::ManuallyDrop;
+use std::ptr::NonNull;
+
+use gpu_alloc_types::{DeviceProperties, MappedMemoryRange, MemoryDevice, MemoryPropertyFlags};
+use vulkanalia::prelude::v1_0::*;
+
+use crate::{
+    align_range_to_atom, memory_type_props, non_coherent_atom_size, VulkanaliaMemoryDevice,
+    VulkanaliaMemoryError,
+};
+
+/// Mapped region of a memory object which keeps host and device views in sync.
+///
+/// Created with [`open_host_mapping`]. The region is invalidated when opened,
+/// and flushed and unmapped on drop or [`close`](Self::close).
+/// For host coherent memory flushes and invalidations are skipped.
+///
+/// Any memory device can be used, e.g. `MockMemoryDevice` with its `u64` memory handles as `M`.
+/// While the mapping is alive the memory object must not be mapped again,
+/// freed or unmapped through other means.
+pub struct HostMapping<'a, D = VulkanaliaMemoryDevice, M = vk::DeviceMemory>
+where
+    D: MemoryDevice<M> + ?Sized,
+    M: Copy,
+{
+    device: &'a D,
+    memory: M,
+    ptr: NonNull<u8>,
+    len: usize,
+    /// Mapped region, aligned with [`align_range_to_atom`].
+    aligned_offset: u64,
+    aligned_size: u64,
+    coherent: bool,
+}
+
+/// Maps `size` bytes of the memory object starting at `offset`,
+/// invalidating them unless the memory type is host coherent.
+///
+/// The mapped, invalidated and flushed region is the requested one
+/// aligned with [`align_range_to_atom`], so it is always valid for non-coherent memory.
+/// Returns [`VulkanaliaMemoryError::NotHostVisible`] without mapping when
+/// the memory type is not host visible, and [`VulkanaliaMemoryError::OutOfBounds`]
+/// when `offset` is not within the memory object.
+///
+/// # Safety
+///
+/// - `memory` must have been allocated from the `device` with `memory_type`
+///   and must not be mapped.
+/// - `props` must be collected for the physical device of the `device`.
+/// - `allocation_size` must be the size of `memory`.
+/// - `offset + size` must not exceed the size of the memory object.
+/// - The device must not access the region while the mapping is alive.
+pub unsafe fn open_host_mapping<'a, D, M>(
+    device: &'a D,
+    props: &DeviceProperties<'_>,
+    memory: M,
+    memory_type: u32,
+    allocation_size: u64,
+    offset: u64,
+    size: u64,
+) -> Result<HostMapping<'a, D, M>, VulkanaliaMemoryError>
+where
+    D: MemoryDevice<M> + ?Sized,
+    M: Copy,
+{
+    let memory_flags = host_visible_flags(props, memory_type)?;
+    let len = usize::try_from(size).map_err(|_| VulkanaliaMemoryError::OutOfHostMemory)?;
+
+    // An empty region still maps one atom, `vkMapMemory` does not accept zero size
+    let (ptr, aligned_offset, aligned_size) =
+        map_from_atom(device, props, memory, allocation_size, offset, size.max(1))?;
+
+    let mapping = HostMapping {
+        device,
+        memory,
+        ptr,
+        len,
+        aligned_offset,
+        aligned_size,
+        coherent: memory_flags.contains(MemoryPropertyFlags::HOST_COHERENT),
+    };
+
+    if !mapping.coherent {
+        if let Err(e) = device.invalidate_memory_ranges(&[mapping.mapped_range()]) {
+            // Nothing was written yet, so there is nothing to flush
+            let mut mapping = ManuallyDrop::new(mapping);
+            device.unmap_memory(&mut mapping.memory);
+            return Err(e.into());
+        }
+    }
+
+    Ok(mapping)
+}
+
+/// Copies `data` into the memory object at `offset`,
+/// mapping it for the copy and flushing unless the memory type is host coherent.
+///
+/// Only the written range aligned with [`align_range_to_atom`] is mapped and flushed.
+/// Returns [`VulkanaliaMemoryError::NotHostVisible`] without mapping when
+/// the memory type is not host visible, and [`VulkanaliaMemoryError::OutOfBounds`]
+/// when `offset` is not within the memory object.
+/// The memory is unmapped on any error after mapping.
+///
+/// Each call maps and unmaps the memory, which suits infrequent uploads.
+/// Keep the memory mapped instead, e.g. with [`open_host_mapping`], for per-frame updates.
+///
+/// # Safety
+///
+/// - `memory` must have been allocated from the `device` with `memory_type`
+///   and must not be mapped.
+/// - `props` must be collected for the physical device of the `device`.
+/// - `allocation_size` must be the size of `memory`.
+/// - `offset + data.len()` must not exceed the size of the memory object.
+/// - The device must not access the written range during the call.
+pub unsafe fn upload_to_memory<D, M>(
+    device: &D,
+    props: &DeviceProperties<'_>,
+    mut memory: M,
+    memory_type: u32,
+    allocation_size: u64,
+    offset: u64,
+    data: &[u8],
+) -> Result<(), VulkanaliaMemoryError>
+where
+    D: MemoryDevice<M> + ?Sized,
+    M: Copy,
+{
+    let memory_flags = host_visible_flags(props, memory_type)?;
+    if data.is_empty() {
+        return Ok(());
+    }
+
+    let (ptr, aligned_offset, aligned_size) = map_from_atom(
+        device,
+        props,
+        memory,
+        allocation_size,
+        offset,
+        data.len() as u64,
+    )?;
+
+    std::ptr::copy_nonoverlapping(data.as_ptr(), ptr.as_ptr(), data.len());
+
+    let flushed = if memory_flags.contains(MemoryPropertyFlags::HOST_COHERENT) {
+        Ok(())
+    } else {
+        let range = MappedMemoryRange {
+            memory: &memory,
+            offset: aligned_offset,
+            size: aligned_size,
+        };
+        device
+            .flush_memory_ranges(&[range])
+            .map_err(VulkanaliaMemoryError::from)
+    };
+
+    device.unmap_memory(&mut memory);
+    flushed
+}
+
+/// Returns property flags of the memory type, failing when it is not host visible.
+fn host_visible_flags(
+    props: &DeviceProperties<'_>,
+    memory_type: u32,
+) -> Result<MemoryPropertyFlags, VulkanaliaMemoryError> {
+    let memory_flags = memory_type_props(props, memory_type);
+
+    if !memory_flags.contains(MemoryPropertyFlags::HOST_VISIBLE) {
+        return Err(VulkanaliaMemoryError::NotHostVisible);
+    }
+    Ok(memory_flags)
+}
+
+/// Maps `[offset, offset + size)` range of the memory object aligned with [`align_range_to_atom`],
+/// returning pointer to `offset` and the aligned range.
+unsafe fn map_from_atom<D, M>(
+    device: &D,
+    props: &DeviceProperties<'_>,
+    mut memory: M,
+    allocation_size: u64,
+    offset: u64,
+    size: u64,
+) -> Result<(NonNull<u8>, u64, u64), VulkanaliaMemoryError>
+where
+    D: MemoryDevice<M> + ?Sized,
+    M: Copy,
+{
+    if offset >= allocation_size {
+        return Err(VulkanaliaMemoryError::OutOfBounds);
+    }
+    let (aligned_offset, aligned_size) =
+        align_range_to_atom(offset, size, non_coherent_atom_size(props), allocation_size);
+
+    let base = device.map_memory(&mut memory, aligned_offset, aligned_size)?;
+    let ptr = NonNull::new_unchecked(base.as_ptr().add((offset - aligned_offset) as usize));
+    Ok((ptr, aligned_offset, aligned_size))
+}
+
+impl<D, M> HostMapping<'_, D, M>
+where
+    D: MemoryDevice<M> + ?Sized,
+    M: Copy,
+{
+    /// Returns pointer to the start of the requested region.
+    pub fn as_ptr(&self) -> *mut u8 {
+        self.ptr.as_ptr()
+    }
+
+    /// Returns the length of the requested region in bytes.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns `true` if the requested region is empty.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns the mapped memory object.
+    pub fn memory(&self) -> M {
+        self.memory
+    }
+
+    /// Returns the requested region as a byte slice.
+    ///
+    /// # Safety
+    ///
+    /// The device must not write to the region while the slice is alive.
+    pub unsafe fn as_slice(&self) -> &[u8] {
+        std::slice::from_raw_parts(self.ptr.as_ptr(), self.len)
+    }
+
+    /// Returns the requested region as a mutable byte slice.
+    ///
+    /// # Safety
+    ///
+    /// The device must not access the region while the slice is alive.
+    pub unsafe fn as_mut_slice(&mut self) -> &mut [u8] {
+        std::slice::from_raw_parts_mut(self.ptr.as_ptr(), self.len)
+    }
+
+    /// Flushes and unmaps the region, returning the flush error if any.
+    ///
+    /// Dropping the mapping does the same, but can only report the error via tracing.
+    pub fn close(self) -> Result<(), VulkanaliaMemoryError> {
+        let mut mapping = ManuallyDrop::new(self);
+        unsafe {
+            // SAFETY: The mapping is not used after this call
+            mapping.release()
+        }
+    }
+
+    unsafe fn release(&mut self) -> Result<(), VulkanaliaMemoryError> {
+        let flushed = if self.coherent {
+            Ok(())
+        } else {
+            self.device
+                .flush_memory_ranges(&[self.mapped_range()])
+                .map_err(VulkanaliaMemoryError::from)
+        };
+
+        self.device.unmap_memory(&mut self.memory);
+        flushed
+    }
+
+    fn mapped_range(&self) -> MappedMemoryRange<'_, M> {
+        MappedMemoryRange {
+            memory: &self.memory,
+            offset: self.aligned_offset,
+            size: self.aligned_size,
+        }
+    }
+}
+
+impl<D, M> Drop for HostMapping<'_, D, M>
+where
+    D: MemoryDevice<M> + ?Sized,
+    M: Copy,
+{
+    fn drop(&mut self) {
+        let result = unsafe {
+            // SAFETY: The memory object was mapped when the mapping was created
+            // and it is still owned by the mapping.
+            self.release()
+        };
+
+        #[cfg(any(feature = "tracing", feature = "log"))]
+        if let Err(e) = result {
+            #[cfg(feature = "tracing")]
+            tracing::error!(target: "gpu_alloc::vulkanalia", %e, "Failed to flush host mapping");
+            #[cfg(feature = "log")]
+            log::error!(target: "gpu_alloc::vulkanalia", "Failed to flush host mapping: {e}");
+        }
+        #[cfg(not(any(feature = "tracing", feature = "log")))]
+        let _ = result;
+    }
+}
+
+#[cfg(all(test, feature = "mock"))]
+mod tests {
+    use std::borrow::Cow;
+
+    use gpu_alloc_types::{AllocationFlags, MemoryHeap, MemoryType};
+
+    use super::*;
+    use crate::MockMemoryDevice;
+
+    const NON_COHERENT: u32 = 0;
+    const COHERENT: u32 = 1;
+    const DEVICE_LOCAL: u32 = 2;
+
+    fn host_visible_props() -> DeviceProperties<'static> {
+        let memory_type = |props| MemoryType { props, heap: 0 };
+        DeviceProperties {
+            memory_types: Cow::Owned(vec![
+                memory_type(MemoryPropertyFlags::HOST_VISIBLE),
+                memory_type(MemoryPropertyFlags::HOST_VISIBLE | MemoryPropertyFlags::HOST_COHERENT),
+                memory_type(MemoryPropertyFlags::DEVICE_LOCAL),
+            ]),
+            memory_heaps: Cow::Owned(vec![MemoryHeap { size: 1 << 30 }]),
+            max_memory_allocation_count: 4096,
+            max_memory_allocation_size: u64::MAX,
+            non_coherent_atom_size: 64,
+            buffer_device_address: false,
+        }
+    }
+
+    /// Reads `len` bytes at `offset` by mapping the memory again.
+    unsafe fn read_back(
+        device: &MockMemoryDevice,
+        mut memory: u64,
+        offset: u64,
+        len: usize,
+    ) -> Vec<u8> {
+        let ptr = device.map_memory(&mut memory, offset, len as u64).unwrap();
+        let data = std::slice::from_raw_parts(ptr.as_ptr(), len).to_vec();
+        device.unmap_memory(&mut memory);
+        data
+    }
+
+    #[test]
+    fn coherent_mapping_skips_flush_and_invalidate() {
+        let device = MockMemoryDevice::new();
+        let props = host_visible_props();
+
+        unsafe {
+            let memory = device
+                .allocate_memory(256, COHERENT, AllocationFlags::empty())
+                .unwrap();
+            let mut mapping =
+                open_host_mapping(&device, &props, memory, COHERENT, 256, 70, 10).unwrap();
+            mapping.as_mut_slice().copy_from_slice(&[1; 10]);
+            mapping.close().unwrap();
+
+            assert_eq!(read_back(&device, memory, 70, 10), [1; 10]);
+            device.deallocate_memory(memory);
+        }
+
+        let calls = device.calls();
+        assert_eq!((calls.invalidate, calls.flush), (0, 0));
+        assert_eq!(calls.map, calls.unmap);
+    }
+
+    #[test]
+    fn non_coherent_mapping_invalidates_and_flushes_once() {
+        let device = MockMemoryDevice::new();
+        let props = host_visible_props();
+
+        unsafe {
+            let memory = device
+                .allocate_memory(256, NON_COHERENT, AllocationFlags::empty())
+                .unwrap();
+            {
+                let mut mapping =
+                    open_host_mapping(&device, &props, memory, NON_COHERENT, 256, 70, 10).unwrap();
+                assert_eq!(mapping.len(), 10);
+                mapping.as_mut_slice().copy_from_slice(&[2; 10]);
+                assert_eq!(device.calls().invalidate, 1);
+            }
+            assert_eq!(device.calls().flush, 1);
+
+            assert_eq!(read_back(&device, memory, 70, 10), [2; 10]);
+            device.deallocate_memory(memory);
+        }
+
+        assert_eq!(device.calls().map, device.calls().unmap);
+    }
+
+    #[test]
+    fn mapping_rejects_bad_memory_types_and_offsets() {
+        let device = MockMemoryDevice::new();
+        let props = host_visible_props();
+
+        unsafe {
+            let memory = device
+                .allocate_memory(256, DEVICE_LOCAL, AllocationFlags::empty())
+                .unwrap();
+            assert!(matches!(
+                open_host_mapping(&device, &props, memory, DEVICE_LOCAL, 256, 0, 16),
+                Err(VulkanaliaMemoryError::NotHostVisible)
+            ));
+            assert!(matches!(
+                open_host_mapping(&device, &props, memory, NON_COHERENT, 256, 256, 0),
+                Err(VulkanaliaMemoryError::OutOfBounds)
+            ));
+            device.deallocate_memory(memory);
+        }
+
+        assert_eq!(device.calls().map, 0);
+    }
+
+    #[test]
+    fn upload_flushes_only_non_coherent_memory() {
+        let device = MockMemoryDevice::new();
+        let props = host_visible_props();
+
+        unsafe {
+            for (memory_type, flushes) in [(COHERENT, 0), (NON_COHERENT, 1)] {
+                let memory = device
+                    .allocate_memory(256, memory_type, AllocationFlags::empty())
+                    .unwrap();
+                let flush = device.calls().flush;
+
+                upload_to_memory(&device, &props, memory, memory_type, 256, 200, &[3; 50]).unwrap();
+                assert_eq!(device.calls().flush - flush, flushes);
+                assert_eq!(read_back(&device, memory, 200, 50), [3; 50]);
+
+                device.deallocate_memory(memory);
+            }
+        }
+
+        let calls = device.calls();
+        assert_eq!(calls.invalidate, 0);
+        assert_eq!(calls.map, calls.unmap);
+    }
+
+    #[test]
+    fn empty_upload_maps_nothing() {
+        let device = MockMemoryDevice::new();
+        let props = host_visible_props();
+
+        unsafe {
+            let memory = device
+                .allocate_memory(256, NON_COHERENT, AllocationFlags::empty())
+                .unwrap();
+            upload_to_memory(&device, &props, memory, NON_COHERENT, 256, 0, &[]).unwrap();
+            device.deallocate_memory(memory);
+        }
+
+        let calls = device.calls();
+        assert_eq!((calls.map, calls.flush), (0, 0));
+    }
+}
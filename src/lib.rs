@@ -1,12 +1,99 @@
 use std::ptr::NonNull;
+use std::sync::{Mutex, MutexGuard};
 
-use gpu_alloc_types::{
+use vulkanalia::prelude::v1_0::*;
+use vulkanalia::vk::InstanceV1_1;
+
+use self::allocate::AllocateExtensions;
+
+// `gpu-alloc-types` items used in the public API are re-exported.
+// Using them through this crate guarantees a compatible version,
+// avoiding trait mismatch errors caused by version skew.
+pub use gpu_alloc_types::{
     AllocationFlags, DeviceMapError, DeviceProperties, MappedMemoryRange, MemoryDevice, MemoryHeap,
     MemoryPropertyFlags, MemoryType, OutOfMemory,
 };
-use smallvec::SmallVec;
-use vulkanalia::prelude::v1_0::*;
-use vulkanalia::vk::InstanceV1_1;
+
+/// The `gpu-alloc` crate this crate is built against.
+#[cfg(feature = "gpu-alloc")]
+pub use gpu_alloc;
+
+pub use self::allocate::{
+    set_memory_priority, AlignedMemory, AllocateInfoBuilder, DedicatedTarget,
+};
+#[cfg(feature = "gpu-alloc")]
+pub use self::allocator::{
+    bind_buffer, bind_image, create_allocator, create_allocator_with_defaults, map_block,
+    usage_flags_for, HostAccess,
+};
+pub use self::cache::DevicePropertiesCache;
+pub use self::configured::ConfiguredMemoryDevice;
+#[cfg(feature = "debug-utils")]
+pub use self::debug_utils::{set_memory_name, SetNameError};
+pub use self::device_address::{
+    get_buffer_device_address, get_buffer_device_address_for_version,
+    supports_buffer_device_address,
+};
+pub use self::error::{
+    describe_map_error, describe_out_of_memory, device_map_error_to_error_code,
+    out_of_memory_to_error_code, DevicePropertiesError, VulkanaliaMemoryError,
+};
+pub use self::host_mapping::{open_host_mapping, upload_to_memory, HostMapping};
+pub use self::mapping::MappedMemory;
+pub use self::memory_types::{
+    amd_coherent_memory_types, amd_coherent_memory_types_from, find_memory_type, heap_flags_from,
+    is_host_coherent, memory_type_cost, memory_type_heap, memory_type_heap_size, memory_type_props,
+    memory_types_matching, requires_flush, AccessIntent, HeapFlags,
+};
+#[cfg(feature = "smallvec")]
+pub use self::memory_types::{bar_memory_types, memory_heap_flags, memory_heap_flags_from};
+#[cfg(feature = "mock")]
+pub use self::mock::{MockCalls, MockMemoryDevice};
+pub use self::owned::OwnedDeviceMemory;
+#[cfg(feature = "smallvec")]
+pub use self::physical_device::supported_extensions;
+pub use self::physical_device::{
+    buffer_image_granularity, external_buffer_properties, external_memory_host_properties,
+    max_buffer_size, memory_budget, needs_granularity_separation, relevant_limits,
+    sparse_properties, MemoryBudget, RelevantLimits, ResourceType, SparseProperties,
+};
+pub use self::ranges::{
+    align_range_to_atom, non_coherent_atom_size, pad_to_non_coherent_atom, to_vk_mapped_range,
+    TypedMemoryRange, DEFAULT_RANGES_INLINE_CAPACITY,
+};
+pub use self::requirements::{
+    buffer_dedicated_requirements, buffer_memory_requirements, buffer_memory_type,
+    image_dedicated_requirements, image_memory_requirements, image_memory_type,
+    DedicatedRequirements,
+};
+#[cfg(feature = "tracking")]
+pub use self::tracking::{Stats, StatsSnapshot, TrackingMemoryDevice};
+#[cfg(feature = "win32")]
+pub use self::win32::Win32Handle;
+
+mod allocate;
+#[cfg(feature = "gpu-alloc")]
+mod allocator;
+mod cache;
+mod configured;
+#[cfg(feature = "debug-utils")]
+mod debug_utils;
+mod device_address;
+mod error;
+mod external;
+mod host_mapping;
+mod mapping;
+mod memory_types;
+#[cfg(feature = "mock")]
+mod mock;
+mod owned;
+mod physical_device;
+mod ranges;
+mod requirements;
+#[cfg(feature = "tracking")]
+mod tracking;
+#[cfg(feature = "win32")]
+mod win32;
 
 /// Vulkan device extension trait which wraps its reference into memory device.
 pub trait AsMemoryDevice {
@@ -21,11 +108,34 @@ impl AsMemoryDevice for Device {
 }
 
 /// A wrapper around Vulkan device which implements [`MemoryDevice`].
+///
+/// # Thread safety
+///
+/// The wrapper is `Send` and `Sync`, and sharing `&VulkanaliaMemoryDevice` between
+/// threads is sound: `vkAllocateMemory` does not require external synchronization
+/// of the device, so memory can be allocated from several threads concurrently.
+///
+/// Operations on a single memory object are not synchronized though.
+/// Per Vulkan spec, `memory` parameter of `vkFreeMemory`, `vkMapMemory` and `vkUnmapMemory`
+/// must be externally synchronized, which is the caller's responsibility.
 #[repr(transparent)]
 pub struct VulkanaliaMemoryDevice {
     device: Device,
 }
 
+const _: fn() = || {
+    fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<VulkanaliaMemoryDevice>();
+};
+
+// `wrap` reinterprets `&Device` as `&VulkanaliaMemoryDevice`, which is only sound
+// while the wrapper holds nothing but the device. Extra state belongs to
+// `ConfiguredMemoryDevice` instead, adding a field here must fail to compile.
+const _: () = {
+    assert!(std::mem::size_of::<VulkanaliaMemoryDevice>() == std::mem::size_of::<Device>());
+    assert!(std::mem::align_of::<VulkanaliaMemoryDevice>() == std::mem::align_of::<Device>());
+};
+
 impl VulkanaliaMemoryDevice {
     pub fn wrap(device: &Device) -> &Self {
         unsafe {
@@ -33,44 +143,77 @@ impl VulkanaliaMemoryDevice {
             &*(device as *const Device).cast::<Self>()
         }
     }
+
+    /// Returns the wrapped device, e.g. to make calls unrelated to memory.
+    pub fn device(&self) -> &Device {
+        &self.device
+    }
+
+    /// Checks a pointer returned by a successful map of the `memory` object,
+    /// unmapping the memory when it is null.
+    ///
+    /// Some drivers return null pointer along with `VK_SUCCESS`.
+    unsafe fn non_null_mapping(
+        &self,
+        memory: vk::DeviceMemory,
+        ptr: *mut std::ffi::c_void,
+    ) -> Option<NonNull<u8>> {
+        let ptr = NonNull::new(ptr.cast::<u8>());
+        if ptr.is_none() {
+            self.device.unmap_memory(memory);
+        }
+        ptr
+    }
+
+    /// Allocates new memory object from device.
+    ///
+    /// Unlike [`MemoryDevice::allocate_memory`], which panics on any error
+    /// except memory exhaustion, this method returns every Vulkan error to the caller.
+    ///
+    /// # Safety
+    ///
+    /// Same as for [`MemoryDevice::allocate_memory`].
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(target = "gpu_alloc::vulkanalia", skip(self))
+    )]
+    pub unsafe fn try_allocate_memory(
+        &self,
+        size: u64,
+        memory_type: u32,
+        flags: AllocationFlags,
+    ) -> Result<vk::DeviceMemory, VulkanaliaMemoryError> {
+        self.allocate_memory_with(size, memory_type, flags, &AllocateExtensions::default())
+    }
 }
 
 impl MemoryDevice<vk::DeviceMemory> for VulkanaliaMemoryDevice {
-    #[cfg_attr(feature = "tracing", tracing::instrument(skip(self)))]
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(target = "gpu_alloc::vulkanalia", skip(self))
+    )]
     unsafe fn allocate_memory(
         &self,
         size: u64,
         memory_type: u32,
         flags: AllocationFlags,
     ) -> Result<vk::DeviceMemory, OutOfMemory> {
-        assert!((flags & !(AllocationFlags::DEVICE_ADDRESS)).is_empty());
-
-        let mut info = vk::MemoryAllocateInfo::builder()
-            .allocation_size(size)
-            .memory_type_index(memory_type);
-
-        let mut info_flags;
-
-        if flags.contains(AllocationFlags::DEVICE_ADDRESS) {
-            info_flags = vk::MemoryAllocateFlagsInfo::builder()
-                .flags(vk::MemoryAllocateFlags::DEVICE_ADDRESS);
-            info = info.push_next(&mut info_flags);
-        }
-
-        match self.device.allocate_memory(&info, None) {
-            Ok(memory) => Ok(memory),
-            Err(vk::ErrorCode::OUT_OF_DEVICE_MEMORY) => Err(OutOfMemory::OutOfDeviceMemory),
-            Err(vk::ErrorCode::OUT_OF_HOST_MEMORY) => Err(OutOfMemory::OutOfHostMemory),
-            Err(e) => panic!("Unexpected Vulkan error: {e}"),
-        }
+        self.try_allocate_memory(size, memory_type, flags)
+            .map_err(out_of_memory)
     }
 
-    #[cfg_attr(feature = "tracing", tracing::instrument(skip(self)))]
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(target = "gpu_alloc::vulkanalia", skip(self))
+    )]
     unsafe fn deallocate_memory(&self, memory: vk::DeviceMemory) {
         self.device.free_memory(memory, None);
     }
 
-    #[cfg_attr(feature = "tracing", tracing::instrument(skip(self)))]
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(target = "gpu_alloc::vulkanalia", skip(self))
+    )]
     unsafe fn map_memory(
         &self,
         memory: &mut vk::DeviceMemory,
@@ -82,73 +225,131 @@ impl MemoryDevice<vk::DeviceMemory> for VulkanaliaMemoryDevice {
             .map_memory(*memory, offset, size, vk::MemoryMapFlags::empty())
         {
             Ok(ptr) => {
-                Ok(NonNull::new(ptr as *mut u8)
-                    .expect("Pointer to memory mapping must not be null"))
+                #[cfg(feature = "tracing")]
+                tracing::trace!(target: "gpu_alloc::vulkanalia", ?ptr, "Memory mapped");
+                #[cfg(feature = "log")]
+                log::trace!(target: "gpu_alloc::vulkanalia", "Memory mapped at {ptr:?}");
+
+                self.non_null_mapping(*memory, ptr)
+                    .ok_or(DeviceMapError::MapFailed)
             }
-            Err(vk::ErrorCode::OUT_OF_DEVICE_MEMORY) => Err(DeviceMapError::OutOfDeviceMemory),
-            Err(vk::ErrorCode::OUT_OF_HOST_MEMORY) => Err(DeviceMapError::OutOfHostMemory),
-            Err(vk::ErrorCode::MEMORY_MAP_FAILED) => Err(DeviceMapError::MapFailed),
-            Err(e) => panic!("Unexpected Vulkan error: {e}"),
+            Err(e) => Err(map_error(e)),
         }
     }
 
-    #[cfg_attr(feature = "tracing", tracing::instrument(skip(self)))]
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(target = "gpu_alloc::vulkanalia", skip(self))
+    )]
     unsafe fn unmap_memory(&self, memory: &mut vk::DeviceMemory) {
         self.device.unmap_memory(*memory);
     }
 
-    #[cfg_attr(feature = "tracing", tracing::instrument(skip(self)))]
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            target = "gpu_alloc::vulkanalia",
+            skip(self, ranges),
+            fields(ranges = ranges.len())
+        )
+    )]
     unsafe fn invalidate_memory_ranges(
         &self,
         ranges: &[MappedMemoryRange<'_, vk::DeviceMemory>],
     ) -> Result<(), OutOfMemory> {
-        self.device
-            .invalidate_mapped_memory_ranges(
-                &ranges
-                    .iter()
-                    .map(|range| {
-                        vk::MappedMemoryRange::builder()
-                            .memory(*range.memory)
-                            .offset(range.offset)
-                            .size(range.size)
-                    })
-                    .collect::<SmallVec<[_; 4]>>(),
-            )
-            .map_err(|e| match e {
-                vk::ErrorCode::OUT_OF_DEVICE_MEMORY => OutOfMemory::OutOfDeviceMemory,
-                vk::ErrorCode::OUT_OF_HOST_MEMORY => OutOfMemory::OutOfHostMemory,
-                e => panic!("Unexpected Vulkan error: {e}"),
-            })
+        self.invalidate_ranges::<DEFAULT_RANGES_INLINE_CAPACITY>(ranges)
     }
 
-    #[cfg_attr(feature = "tracing", tracing::instrument(skip(self)))]
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            target = "gpu_alloc::vulkanalia",
+            skip(self, ranges),
+            fields(ranges = ranges.len())
+        )
+    )]
     unsafe fn flush_memory_ranges(
         &self,
         ranges: &[MappedMemoryRange<'_, vk::DeviceMemory>],
     ) -> Result<(), OutOfMemory> {
-        self.device
-            .flush_mapped_memory_ranges(
-                &ranges
-                    .iter()
-                    .map(|range| {
-                        vk::MappedMemoryRange::builder()
-                            .memory(*range.memory)
-                            .offset(range.offset)
-                            .size(range.size)
-                    })
-                    .collect::<SmallVec<[_; 4]>>(),
-            )
-            .map_err(|e| match e {
-                vk::ErrorCode::OUT_OF_DEVICE_MEMORY => OutOfMemory::OutOfDeviceMemory,
-                vk::ErrorCode::OUT_OF_HOST_MEMORY => OutOfMemory::OutOfHostMemory,
-                e => panic!("Unexpected Vulkan error: {e}"),
-            })
+        self.flush_ranges::<DEFAULT_RANGES_INLINE_CAPACITY>(ranges)
+    }
+}
+
+/// Converts allocation error into `gpu-alloc-types` error,
+/// handling unexpected errors with [`unexpected_error`].
+#[track_caller]
+fn out_of_memory(e: VulkanaliaMemoryError) -> OutOfMemory {
+    match e {
+        VulkanaliaMemoryError::OutOfDeviceMemory
+        | VulkanaliaMemoryError::ExceedsMaxAllocationSize => OutOfMemory::OutOfDeviceMemory,
+        VulkanaliaMemoryError::OutOfHostMemory => OutOfMemory::OutOfHostMemory,
+        // Expected during capture replay sessions, so it must not abort the process
+        VulkanaliaMemoryError::InvalidOpaqueCaptureAddress => {
+            #[cfg(feature = "tracing")]
+            tracing::warn!(
+                target: "gpu_alloc::vulkanalia",
+                "Opaque capture address is not available, reporting out of host memory"
+            );
+            #[cfg(feature = "log")]
+            log::warn!(
+                target: "gpu_alloc::vulkanalia",
+                "Opaque capture address is not available, reporting out of host memory"
+            );
+            e.into()
+        }
+        e => unexpected_error(e, OutOfMemory::OutOfDeviceMemory),
     }
 }
 
+/// Converts mapping error code into `gpu-alloc-types` error,
+/// handling unexpected errors with [`unexpected_error`].
+#[track_caller]
+fn map_error(e: vk::ErrorCode) -> DeviceMapError {
+    match e {
+        vk::ErrorCode::OUT_OF_DEVICE_MEMORY => DeviceMapError::OutOfDeviceMemory,
+        vk::ErrorCode::OUT_OF_HOST_MEMORY => DeviceMapError::OutOfHostMemory,
+        vk::ErrorCode::MEMORY_MAP_FAILED => DeviceMapError::MapFailed,
+        e => unexpected_error(e, DeviceMapError::MapFailed),
+    }
+}
+
+/// Handles an error code `gpu-alloc-types` errors have no variant for.
+///
+/// Panics by default. With `no-panic` feature the error is reported via tracing
+/// and `fallback` is returned instead, trading debuggability of driver issues
+/// for not aborting the process.
+#[cfg(not(feature = "no-panic"))]
+#[cold]
+#[track_caller]
+fn unexpected_error<E>(e: impl std::fmt::Display, _fallback: E) -> E {
+    panic!("Unexpected Vulkan error: {e}")
+}
+
+#[cfg(feature = "no-panic")]
+#[cold]
+fn unexpected_error<E>(e: impl std::fmt::Display, fallback: E) -> E {
+    #[cfg(feature = "tracing")]
+    tracing::error!(target: "gpu_alloc::vulkanalia", "Unexpected Vulkan error: {e}");
+    #[cfg(feature = "log")]
+    log::error!(target: "gpu_alloc::vulkanalia", "Unexpected Vulkan error: {e}");
+    #[cfg(not(any(feature = "tracing", feature = "log")))]
+    let _ = e;
+    fallback
+}
+
+/// Locks a mutex whose contents are always left in a consistent state,
+/// so a poisoned lock is still usable.
+pub(crate) fn lock_ignoring_poison<T: ?Sized>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|e| e.into_inner())
+}
+
 /// Collects device properties from vulkanalia's `Instance` for the specified
 /// physical device, required to create `GpuAllocator`.
 ///
+/// When `max_memory_allocation_size` cannot be queried (Vulkan 1.0 without
+/// "VK_KHR_get_physical_device_properties2" or "VK_KHR_maintenance3"),
+/// it is capped to the size of the smallest memory heap.
 ///
 /// # Safety
 ///
@@ -165,102 +366,390 @@ pub unsafe fn device_properties(
     version: u32,
     physical_device: vk::PhysicalDevice,
 ) -> VkResult<DeviceProperties<'static>> {
-    struct ExtInfo {
-        buffer_device_address: bool,
-        max_memory_allocation_size: u64,
+    collect_device_properties(instance, version, physical_device, QueryOptions::default())
+        .map(|(properties, _)| properties)
+}
+
+/// Collects device properties like [`device_properties`] does,
+/// but first checks `version` against `api_version`, the version
+/// the `instance` was created with.
+///
+/// Only major and minor versions are compared. The check is done before any query,
+/// so a too high `version` never selects a wrong query path.
+///
+/// # Safety
+///
+/// Same as for [`device_properties`], except for the `version` requirement.
+/// `api_version` must be the `ApplicationInfo::api_version` the `instance` was created with.
+pub unsafe fn try_device_properties(
+    instance: &Instance,
+    api_version: u32,
+    version: u32,
+    physical_device: vk::PhysicalDevice,
+) -> Result<DeviceProperties<'static>, DevicePropertiesError> {
+    check_version(version, api_version)?;
+    Ok(device_properties(instance, version, physical_device)?)
+}
+
+/// Fails with [`DevicePropertiesError::VersionTooHigh`] when major and minor
+/// versions of `version` are higher than the ones of `api_version`.
+fn check_version(version: u32, api_version: u32) -> Result<(), DevicePropertiesError> {
+    let major_minor = |version: u32| (vk::version_major(version), vk::version_minor(version));
+    if major_minor(version) > major_minor(api_version) {
+        return Err(DevicePropertiesError::VersionTooHigh {
+            requested: version,
+            instance: api_version,
+        });
     }
+    Ok(())
+}
 
-    let memory_properties = instance.get_physical_device_memory_properties(physical_device);
+/// Collects device properties of every physical device of the `instance`
+/// with [`device_properties`], e.g. to pick the most suitable one.
+///
+/// Stops at the first error.
+///
+/// # Safety
+///
+/// `version` must not be higher than the `api_version` of the `instance`.
+/// For each entry, `buffer_device_address` has the same caveat as
+/// for [`device_properties`].
+pub unsafe fn all_device_properties(
+    instance: &Instance,
+    version: u32,
+) -> VkResult<Vec<(vk::PhysicalDevice, DeviceProperties<'static>)>> {
+    instance
+        .enumerate_physical_devices()?
+        .into_iter()
+        .map(|physical_device| {
+            let properties = device_properties(instance, version, physical_device)?;
+            Ok((physical_device, properties))
+        })
+        .collect()
+}
 
-    // Determine what to fetch by instance version and device features
-    let (query_props, query_features) = 'query: {
-        let mut required_extensions: [_; 2] = [
-            Some(&vk::KHR_MAINTENANCE3_EXTENSION.name),
-            Some(&vk::KHR_BUFFER_DEVICE_ADDRESS_EXTENSION.name),
-        ];
-
-        match vk::version_minor(version) {
-            0 => {
-                if !instance
-                    .extensions()
-                    .contains(&vk::KHR_GET_PHYSICAL_DEVICE_PROPERTIES2_EXTENSION.name)
-                {
-                    // Required devices extensions cannot be queried
-                    break 'query (false, false);
-                }
-            }
-            1 => {
-                // `max_memory_allocation_size` is mandatory since 1.1
-                required_extensions[1] = None;
-            }
-            _ => {
-                // `PhysicalDeviceBufferDeviceAddressFeatures` is mandatory since 1.2
-                required_extensions = [None, None];
+/// Collects device properties like [`device_properties`] does,
+/// but uses the caller-supplied `features` instead of querying them.
+///
+/// This avoids a redundant `vkGetPhysicalDeviceFeatures2` call when the application
+/// has already queried `PhysicalDeviceBufferDeviceAddressFeatures` as part of its own chain.
+///
+/// # Safety
+///
+/// Same as for [`device_properties`].
+/// The caller is responsible for `features` being accurate for the `physical_device`.
+pub unsafe fn device_properties_from_features(
+    instance: &Instance,
+    version: u32,
+    physical_device: vk::PhysicalDevice,
+    features: &vk::PhysicalDeviceBufferDeviceAddressFeatures,
+) -> VkResult<DeviceProperties<'static>> {
+    let options = QueryOptions {
+        bda_features: Some(features),
+        ..Default::default()
+    };
+    collect_device_properties(instance, version, physical_device, options)
+        .map(|(properties, _)| properties)
+}
+
+/// Collects device properties like [`device_properties`] does,
+/// but prefers structures promoted to core Vulkan over extension ones.
+///
+/// For Vulkan 1.2 and above, `max_memory_allocation_size` is read from
+/// `PhysicalDeviceVulkan11Properties` instead of `PhysicalDeviceMaintenance3Properties`.
+/// For Vulkan 1.0 and 1.1 this is the same as [`device_properties`].
+///
+/// # Safety
+///
+/// Same as for [`device_properties`].
+pub unsafe fn device_properties_v13(
+    instance: &Instance,
+    version: u32,
+    physical_device: vk::PhysicalDevice,
+) -> VkResult<DeviceProperties<'static>> {
+    let options = QueryOptions {
+        promoted_properties: true,
+        ..Default::default()
+    };
+    collect_device_properties(instance, version, physical_device, options)
+        .map(|(properties, _)| properties)
+}
+
+/// Collects device properties like [`device_properties`] does,
+/// also reporting which query paths were used.
+///
+/// # Safety
+///
+/// Same as for [`device_properties`].
+pub unsafe fn device_properties_detailed(
+    instance: &Instance,
+    version: u32,
+    physical_device: vk::PhysicalDevice,
+) -> VkResult<(DeviceProperties<'static>, PropertiesQueryInfo)> {
+    collect_device_properties(instance, version, physical_device, QueryOptions::default())
+}
+
+/// Describes how device properties were collected,
+/// e.g. to explain why `buffer_device_address` is `false`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct PropertiesQueryInfo {
+    /// Limits were queried with `vkGetPhysicalDeviceProperties2`.
+    pub used_properties2: bool,
+    /// `max_memory_allocation_size` was read from `PhysicalDeviceMaintenance3Properties`.
+    pub used_maintenance3: bool,
+    /// Features were queried with `vkGetPhysicalDeviceFeatures2`.
+    pub used_features2: bool,
+    /// `buffer_device_address` reflects the device's `bufferDeviceAddress` feature.
+    /// When `false`, the feature could not be queried and is reported as unsupported.
+    ///
+    /// This happens for Vulkan 1.0 without instance extension
+    /// "VK_KHR_get_physical_device_properties2", and for Vulkan prior 1.2 when the device
+    /// does not support extension "VK_KHR_buffer_device_address".
+    /// With `tracing` feature the skipped query is also reported with a debug event.
+    pub buffer_device_address_queried: bool,
+}
+
+/// Collects device properties like [`device_properties`] does,
+/// but uses the caller-supplied extension `support` instead of enumerating
+/// device extensions.
+///
+/// # Safety
+///
+/// Same as for [`device_properties`].
+/// The caller is responsible for `support` being accurate for the `instance`
+/// and the `physical_device`.
+pub unsafe fn device_properties_with_support(
+    instance: &Instance,
+    version: u32,
+    physical_device: vk::PhysicalDevice,
+    support: ExtensionSupport,
+) -> VkResult<DeviceProperties<'static>> {
+    let options = QueryOptions {
+        extension_support: Some(support),
+        ..Default::default()
+    };
+    collect_device_properties(instance, version, physical_device, options)
+        .map(|(properties, _)| properties)
+}
+
+/// Extensions used to collect device properties, see [`device_properties_with_support`].
+///
+/// Flags for functionality promoted to core in the requested version are ignored.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ExtensionSupport {
+    /// Instance extension "VK_KHR_get_physical_device_properties2" is enabled.
+    /// Core since Vulkan 1.1.
+    pub properties2: bool,
+    /// Device extension "VK_KHR_maintenance3" is supported.
+    /// Core since Vulkan 1.1.
+    pub maintenance3: bool,
+    /// Device extension "VK_KHR_buffer_device_address" is supported.
+    /// Core since Vulkan 1.2.
+    pub buffer_device_address: bool,
+}
+
+#[derive(Default)]
+struct QueryOptions<'a> {
+    /// Use the supplied features instead of querying them.
+    bda_features: Option<&'a vk::PhysicalDeviceBufferDeviceAddressFeatures>,
+    /// Query `PhysicalDeviceVulkan11Properties` when available.
+    promoted_properties: bool,
+    /// Use the supplied extension support instead of enumerating device extensions.
+    extension_support: Option<ExtensionSupport>,
+}
+
+unsafe fn query_extension_support(
+    instance: &Instance,
+    version: u32,
+    physical_device: vk::PhysicalDevice,
+) -> VkResult<ExtensionSupport> {
+    let mut supported = [false; 2];
+
+    match vk::version_minor(version) {
+        0 => {
+            if !instance
+                .extensions()
+                .contains(&vk::KHR_GET_PHYSICAL_DEVICE_PROPERTIES2_EXTENSION.name)
+            {
+                // Required devices extensions cannot be queried
+                return Ok(ExtensionSupport::default());
             }
+
+            physical_device::check_extensions(
+                instance,
+                physical_device,
+                &[
+                    &vk::KHR_MAINTENANCE3_EXTENSION.name,
+                    &vk::KHR_BUFFER_DEVICE_ADDRESS_EXTENSION.name,
+                ],
+                &mut supported,
+            )?;
         }
+        1 => {
+            // `max_memory_allocation_size` is mandatory since 1.1
+            supported[0] = true;
 
-        if required_extensions.iter().any(Option::is_some) {
-            let extensions =
-                instance.enumerate_device_extension_properties(physical_device, None)?;
-
-            // Check whether all required extensions are supported
-            let mut to_find = required_extensions.len();
-            'extensions: for extension in extensions {
-                if to_find == 0 {
-                    break 'extensions;
-                }
-
-                for required in required_extensions.iter_mut() {
-                    if let Some(name) = *required {
-                        if name == &extension.extension_name {
-                            *required = None;
-                            to_find -= 1;
-                            continue 'extensions;
-                        }
-                    }
-                }
-            }
+            physical_device::check_extensions(
+                instance,
+                physical_device,
+                &[&vk::KHR_BUFFER_DEVICE_ADDRESS_EXTENSION.name],
+                &mut supported[1..],
+            )?;
+        }
+        _ => {
+            // Everything required is mandatory since 1.2
+            supported = [true, true];
+        }
+    }
 
-            let [limits_ext, bda_ext] = required_extensions;
-            (limits_ext.is_none(), bda_ext.is_none())
-        } else {
-            (true, true)
+    let [maintenance3, buffer_device_address] = supported;
+    Ok(ExtensionSupport {
+        properties2: true,
+        maintenance3,
+        buffer_device_address,
+    })
+}
+
+/// Queries done by [`collect_device_properties`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct QueryPlan {
+    /// `vkGetPhysicalDeviceProperties2` and `vkGetPhysicalDeviceFeatures2` are available.
+    properties2: bool,
+    /// `max_memory_allocation_size` is queried, otherwise it is capped to the smallest heap.
+    query_props: bool,
+    /// `bufferDeviceAddress` feature is queried.
+    query_features: bool,
+    /// `max_memory_allocation_size` is read from `PhysicalDeviceVulkan11Properties`
+    /// instead of `PhysicalDeviceMaintenance3Properties`.
+    use_vulkan11: bool,
+}
+
+impl QueryPlan {
+    fn new(version: u32, support: ExtensionSupport, promoted_properties: bool) -> Self {
+        let minor = vk::version_minor(version);
+        // `vkGetPhysicalDeviceProperties2` and `max_memory_allocation_size` are mandatory since 1.1,
+        // `PhysicalDeviceBufferDeviceAddressFeatures` is mandatory since 1.2
+        let properties2 = minor >= 1 || support.properties2;
+
+        QueryPlan {
+            properties2,
+            query_props: properties2 && (minor >= 1 || support.maintenance3),
+            query_features: properties2 && (minor >= 2 || support.buffer_device_address),
+            use_vulkan11: promoted_properties && minor >= 2,
+        }
+    }
+
+    /// Describes the queries, `features_supplied` when the caller supplied
+    /// `PhysicalDeviceBufferDeviceAddressFeatures` instead of querying them.
+    fn query_info(&self, features_supplied: bool) -> PropertiesQueryInfo {
+        PropertiesQueryInfo {
+            used_properties2: self.query_props,
+            used_maintenance3: self.query_props && !self.use_vulkan11,
+            used_features2: self.query_features && !features_supplied,
+            buffer_device_address_queried: self.query_features || features_supplied,
         }
+    }
+}
+
+/// Returns size of the smallest memory heap, or `u64::MAX` without heaps.
+fn smallest_heap_size(memory_properties: &vk::PhysicalDeviceMemoryProperties) -> u64 {
+    let count = (memory_properties.memory_heap_count as usize).min(vk::MAX_MEMORY_HEAPS);
+
+    memory_properties.memory_heaps[..count]
+        .iter()
+        .map(|memory_heap| memory_heap.size)
+        .min()
+        .unwrap_or(u64::MAX)
+}
+
+unsafe fn collect_device_properties(
+    instance: &Instance,
+    version: u32,
+    physical_device: vk::PhysicalDevice,
+    options: QueryOptions<'_>,
+) -> VkResult<(DeviceProperties<'static>, PropertiesQueryInfo)> {
+    struct ExtInfo {
+        buffer_device_address: bool,
+        max_memory_allocation_size: u64,
+    }
+
+    let memory_properties = instance.get_physical_device_memory_properties(physical_device);
+
+    // Determine what to fetch by instance version and device features
+    let support = match options.extension_support {
+        Some(support) => support,
+        None => query_extension_support(instance, version, physical_device)?,
     };
 
+    let plan = QueryPlan::new(version, support, options.promoted_properties);
+    let query_info = plan.query_info(options.bda_features.is_some());
+
     let mut ext_info = ExtInfo {
         buffer_device_address: false,
         max_memory_allocation_size: u64::MAX,
     };
 
     // Query physical device properties
-    let limits = if query_props {
+    let limits = if plan.query_props {
+        let use_vulkan11 = plan.use_vulkan11;
+
         let mut properties = vk::PhysicalDeviceProperties2::builder();
         let mut maintenance3 = vk::PhysicalDeviceMaintenance3Properties::builder();
-        properties = properties.push_next(&mut maintenance3);
+        let mut vulkan11 = vk::PhysicalDeviceVulkan11Properties::builder();
+        if use_vulkan11 {
+            properties = properties.push_next(&mut vulkan11);
+        } else {
+            properties = properties.push_next(&mut maintenance3);
+        }
         instance.get_physical_device_properties2(physical_device, &mut properties);
 
         let limits = properties.properties.limits;
-        ext_info.max_memory_allocation_size = maintenance3.max_memory_allocation_size;
+        ext_info.max_memory_allocation_size = if use_vulkan11 {
+            vulkan11.max_memory_allocation_size
+        } else {
+            maintenance3.max_memory_allocation_size
+        };
         limits
     } else {
+        // Without maintenance3 the limit is unknown, so conservatively assume
+        // that no single allocation can be larger than the smallest heap
+        ext_info.max_memory_allocation_size = smallest_heap_size(&memory_properties);
+
         instance
             .get_physical_device_properties(physical_device)
             .limits
     };
 
     // Query physical device features
-    if query_features {
+    if let Some(bda_features) = options.bda_features {
+        ext_info.buffer_device_address = bda_features.buffer_device_address != 0;
+    } else if plan.query_features {
         let mut features = vk::PhysicalDeviceFeatures2::builder();
         let mut bda_features = vk::PhysicalDeviceBufferDeviceAddressFeatures::builder();
         features = features.push_next(&mut bda_features);
         instance.get_physical_device_features2(physical_device, &mut features);
 
         ext_info.buffer_device_address = bda_features.buffer_device_address != 0;
-    };
+    } else {
+        #[cfg(feature = "tracing")]
+        tracing::debug!(
+            target: "gpu_alloc::vulkanalia",
+            properties2 = plan.properties2,
+            buffer_device_address_extension = support.buffer_device_address,
+            "Skipped bufferDeviceAddress feature query, reporting it as unsupported"
+        );
+        #[cfg(feature = "log")]
+        log::debug!(
+            target: "gpu_alloc::vulkanalia",
+            "Skipped bufferDeviceAddress feature query, reporting it as unsupported \
+             (properties2: {}, extension: {})",
+            plan.properties2,
+            support.buffer_device_address
+        );
+    }
 
     // Make device properties
-    Ok(DeviceProperties {
+    let properties = DeviceProperties {
         memory_types: memory_properties.memory_types
             [..memory_properties.memory_type_count as usize]
             .iter()
@@ -280,47 +769,506 @@ pub unsafe fn device_properties(
         max_memory_allocation_size: ext_info.max_memory_allocation_size,
         non_coherent_atom_size: limits.non_coherent_atom_size,
         buffer_device_address: ext_info.buffer_device_address,
-    })
+    };
+
+    Ok((properties, query_info))
 }
 
-/// Maps `vulkanalia`'s `MemoryPropertyFlags` to `gpu-alloc-types`.
-pub fn memory_properties_from(props: vk::MemoryPropertyFlags) -> MemoryPropertyFlags {
-    let mut result = MemoryPropertyFlags::empty();
-    if props.contains(vk::MemoryPropertyFlags::DEVICE_LOCAL) {
-        result |= MemoryPropertyFlags::DEVICE_LOCAL;
+/// Collects device properties like [`device_properties`] does,
+/// but allows to override some of the queried values.
+///
+/// Overrides take precedence over the queried values.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct DevicePropertiesBuilder {
+    buffer_device_address: Option<bool>,
+    max_memory_allocation_size: Option<u64>,
+    max_memory_allocation_count: Option<u32>,
+}
+
+impl DevicePropertiesBuilder {
+    /// Creates a builder without overrides.
+    pub fn new() -> Self {
+        Self::default()
     }
-    if props.contains(vk::MemoryPropertyFlags::HOST_VISIBLE) {
-        result |= MemoryPropertyFlags::HOST_VISIBLE;
+
+    /// Overrides `buffer_device_address` field of the resulting properties.
+    ///
+    /// Set it to `false` when the `bufferDeviceAddress` feature
+    /// was not enabled on device creation.
+    pub fn force_buffer_device_address(mut self, enabled: bool) -> Self {
+        self.buffer_device_address = Some(enabled);
+        self
     }
-    if props.contains(vk::MemoryPropertyFlags::HOST_COHERENT) {
-        result |= MemoryPropertyFlags::HOST_COHERENT;
+
+    /// Overrides `max_memory_allocation_size` field of the resulting properties.
+    pub fn override_max_allocation_size(mut self, size: u64) -> Self {
+        self.max_memory_allocation_size = Some(size);
+        self
     }
-    if props.contains(vk::MemoryPropertyFlags::HOST_CACHED) {
-        result |= MemoryPropertyFlags::HOST_CACHED;
+
+    /// Overrides `max_memory_allocation_count` field of the resulting properties.
+    pub fn override_max_allocation_count(mut self, count: u32) -> Self {
+        self.max_memory_allocation_count = Some(count);
+        self
     }
-    if props.contains(vk::MemoryPropertyFlags::LAZILY_ALLOCATED) {
-        result |= MemoryPropertyFlags::LAZILY_ALLOCATED;
+
+    /// Queries device properties and applies the overrides.
+    ///
+    /// # Safety
+    ///
+    /// Same as for [`device_properties`], except for the `buffer_device_address` requirement
+    /// when it was overridden with [`force_buffer_device_address`]:
+    /// - `force_buffer_device_address(false)` lifts it entirely.
+    /// - `force_buffer_device_address(true)` requires the feature to be supported
+    ///   and enabled on device creation, regardless of the queried value.
+    ///
+    /// Size and count overrides must not exceed the device limits.
+    ///
+    /// [`force_buffer_device_address`]: Self::force_buffer_device_address
+    pub unsafe fn build(
+        &self,
+        instance: &Instance,
+        version: u32,
+        physical_device: vk::PhysicalDevice,
+    ) -> VkResult<DeviceProperties<'static>> {
+        let properties = device_properties(instance, version, physical_device)?;
+        Ok(self.apply(properties))
     }
-    result
+
+    /// Replaces the fields of `properties` with the overridden values.
+    fn apply<'a>(&self, mut properties: DeviceProperties<'a>) -> DeviceProperties<'a> {
+        if let Some(enabled) = self.buffer_device_address {
+            properties.buffer_device_address = enabled;
+        }
+        if let Some(size) = self.max_memory_allocation_size {
+            properties.max_memory_allocation_size = size;
+        }
+        if let Some(count) = self.max_memory_allocation_count {
+            properties.max_memory_allocation_count = count;
+        }
+        properties
+    }
+}
+
+/// Maps `vulkanalia`'s `MemoryPropertyFlags` to `gpu-alloc-types`.
+///
+/// Flags without `gpu-alloc-types` counterpart are dropped,
+/// see [`amd_coherent_memory_types`] for the AMD specific ones.
+pub fn memory_properties_from(props: vk::MemoryPropertyFlags) -> MemoryPropertyFlags {
+    MEMORY_PROPERTY_FLAGS
+        .iter()
+        .filter(|(vk_flag, _)| props.contains(*vk_flag))
+        .fold(MemoryPropertyFlags::empty(), |result, (_, flag)| {
+            result | *flag
+        })
 }
 
 /// Maps `gpu-alloc-types`' `MemoryPropertyFlags` to `vulkanalia`.
 pub fn memory_properties_to(props: MemoryPropertyFlags) -> vk::MemoryPropertyFlags {
-    let mut result = vk::MemoryPropertyFlags::empty();
-    if props.contains(MemoryPropertyFlags::DEVICE_LOCAL) {
-        result |= vk::MemoryPropertyFlags::DEVICE_LOCAL;
+    MEMORY_PROPERTY_FLAGS
+        .iter()
+        .filter(|(_, flag)| props.contains(*flag))
+        .fold(vk::MemoryPropertyFlags::empty(), |result, (vk_flag, _)| {
+            result | *vk_flag
+        })
+}
+
+/// Memory property flags known to both `vulkanalia` and `gpu-alloc-types`.
+const MEMORY_PROPERTY_FLAGS: [(vk::MemoryPropertyFlags, MemoryPropertyFlags); 6] = [
+    (
+        vk::MemoryPropertyFlags::DEVICE_LOCAL,
+        MemoryPropertyFlags::DEVICE_LOCAL,
+    ),
+    (
+        vk::MemoryPropertyFlags::HOST_VISIBLE,
+        MemoryPropertyFlags::HOST_VISIBLE,
+    ),
+    (
+        vk::MemoryPropertyFlags::HOST_COHERENT,
+        MemoryPropertyFlags::HOST_COHERENT,
+    ),
+    (
+        vk::MemoryPropertyFlags::HOST_CACHED,
+        MemoryPropertyFlags::HOST_CACHED,
+    ),
+    (
+        vk::MemoryPropertyFlags::LAZILY_ALLOCATED,
+        MemoryPropertyFlags::LAZILY_ALLOCATED,
+    ),
+    (
+        vk::MemoryPropertyFlags::PROTECTED,
+        MemoryPropertyFlags::PROTECTED,
+    ),
+];
+
+#[cfg(test)]
+mod tests {
+    use std::borrow::Cow;
+
+    use super::*;
+
+    fn queried_properties() -> DeviceProperties<'static> {
+        DeviceProperties {
+            memory_types: Cow::Owned(vec![MemoryType {
+                props: MemoryPropertyFlags::DEVICE_LOCAL,
+                heap: 0,
+            }]),
+            memory_heaps: Cow::Owned(vec![MemoryHeap { size: 8 << 30 }]),
+            max_memory_allocation_count: 4096,
+            max_memory_allocation_size: 4 << 30,
+            non_coherent_atom_size: 64,
+            buffer_device_address: true,
+        }
+    }
+
+    #[test]
+    fn out_of_memory_keeps_memory_exhaustion() {
+        assert!(matches!(
+            out_of_memory(VulkanaliaMemoryError::OutOfDeviceMemory),
+            OutOfMemory::OutOfDeviceMemory
+        ));
+        assert!(matches!(
+            out_of_memory(VulkanaliaMemoryError::OutOfHostMemory),
+            OutOfMemory::OutOfHostMemory
+        ));
+        assert!(matches!(
+            out_of_memory(VulkanaliaMemoryError::ExceedsMaxAllocationSize),
+            OutOfMemory::OutOfDeviceMemory
+        ));
+        assert!(matches!(
+            out_of_memory(VulkanaliaMemoryError::InvalidOpaqueCaptureAddress),
+            OutOfMemory::OutOfHostMemory
+        ));
+    }
+
+    #[cfg(not(feature = "no-panic"))]
+    #[test]
+    #[should_panic(expected = "Unexpected Vulkan error")]
+    fn out_of_memory_panics_on_unexpected_error() {
+        out_of_memory(VulkanaliaMemoryError::Other(vk::ErrorCode::DEVICE_LOST));
+    }
+
+    #[cfg(feature = "no-panic")]
+    #[test]
+    fn out_of_memory_falls_back_on_unexpected_error() {
+        assert!(matches!(
+            out_of_memory(VulkanaliaMemoryError::Other(vk::ErrorCode::DEVICE_LOST)),
+            OutOfMemory::OutOfDeviceMemory
+        ));
+    }
+
+    #[test]
+    fn map_error_keeps_map_failures() {
+        assert!(matches!(
+            map_error(vk::ErrorCode::OUT_OF_DEVICE_MEMORY),
+            DeviceMapError::OutOfDeviceMemory
+        ));
+        assert!(matches!(
+            map_error(vk::ErrorCode::OUT_OF_HOST_MEMORY),
+            DeviceMapError::OutOfHostMemory
+        ));
+        assert!(matches!(
+            map_error(vk::ErrorCode::MEMORY_MAP_FAILED),
+            DeviceMapError::MapFailed
+        ));
+    }
+
+    #[cfg(not(feature = "no-panic"))]
+    #[test]
+    #[should_panic(expected = "Unexpected Vulkan error")]
+    fn map_error_panics_on_unexpected_error() {
+        map_error(vk::ErrorCode::DEVICE_LOST);
+    }
+
+    #[cfg(feature = "no-panic")]
+    #[test]
+    fn map_error_falls_back_on_unexpected_error() {
+        assert!(matches!(
+            map_error(vk::ErrorCode::DEVICE_LOST),
+            DeviceMapError::MapFailed
+        ));
     }
-    if props.contains(MemoryPropertyFlags::HOST_VISIBLE) {
-        result |= vk::MemoryPropertyFlags::HOST_VISIBLE;
+
+    #[test]
+    fn memory_property_flags_round_trip() {
+        for subset in 0..1u32 << MEMORY_PROPERTY_FLAGS.len() {
+            let (vk_flags, flags) = MEMORY_PROPERTY_FLAGS
+                .iter()
+                .enumerate()
+                .filter(|(i, _)| subset & (1 << i) != 0)
+                .fold(
+                    (
+                        vk::MemoryPropertyFlags::empty(),
+                        MemoryPropertyFlags::empty(),
+                    ),
+                    |(vk_result, result), (_, (vk_flag, flag))| {
+                        (vk_result | *vk_flag, result | *flag)
+                    },
+                );
+
+            assert_eq!(memory_properties_from(vk_flags), flags);
+            assert_eq!(memory_properties_to(flags), vk_flags);
+            assert_eq!(
+                memory_properties_to(memory_properties_from(vk_flags)),
+                vk_flags
+            );
+        }
     }
-    if props.contains(MemoryPropertyFlags::HOST_COHERENT) {
-        result |= vk::MemoryPropertyFlags::HOST_COHERENT;
+
+    #[test]
+    fn memory_property_flags_table_covers_all_flags() {
+        let flags =
+            MEMORY_PROPERTY_FLAGS
+                .iter()
+                .fold(MemoryPropertyFlags::empty(), |result, (_, flag)| {
+                    assert!(!result.intersects(*flag), "{flag:?} is listed twice");
+                    result | *flag
+                });
+        assert_eq!(flags, MemoryPropertyFlags::all());
+    }
+
+    #[test]
+    fn memory_property_flags_drop_unknown_flags() {
+        let vk_flags = vk::MemoryPropertyFlags::HOST_VISIBLE
+            | vk::MemoryPropertyFlags::DEVICE_COHERENT_AMD
+            | vk::MemoryPropertyFlags::DEVICE_UNCACHED_AMD
+            | vk::MemoryPropertyFlags::RDMA_CAPABLE_NV;
+        assert_eq!(
+            memory_properties_from(vk_flags),
+            MemoryPropertyFlags::HOST_VISIBLE
+        );
+    }
+
+    #[test]
+    fn memory_property_flags_include_protected() {
+        assert_eq!(
+            memory_properties_from(vk::MemoryPropertyFlags::PROTECTED),
+            MemoryPropertyFlags::PROTECTED
+        );
+        assert_eq!(
+            memory_properties_to(MemoryPropertyFlags::PROTECTED),
+            vk::MemoryPropertyFlags::PROTECTED
+        );
     }
-    if props.contains(MemoryPropertyFlags::HOST_CACHED) {
-        result |= vk::MemoryPropertyFlags::HOST_CACHED;
+
+    #[test]
+    fn builder_without_overrides_keeps_queried_values() {
+        let properties = DevicePropertiesBuilder::new().apply(queried_properties());
+
+        assert!(properties.buffer_device_address);
+        assert_eq!(properties.max_memory_allocation_size, 4 << 30);
+        assert_eq!(properties.max_memory_allocation_count, 4096);
+    }
+
+    #[test]
+    fn builder_overrides_take_precedence() {
+        let properties = DevicePropertiesBuilder::new()
+            .force_buffer_device_address(false)
+            .override_max_allocation_size(1 << 30)
+            .override_max_allocation_count(64)
+            .apply(queried_properties());
+
+        assert!(!properties.buffer_device_address);
+        assert_eq!(properties.max_memory_allocation_size, 1 << 30);
+        assert_eq!(properties.max_memory_allocation_count, 64);
+        // Fields without overrides are left as queried
+        assert_eq!(properties.non_coherent_atom_size, 64);
+        assert_eq!(properties.memory_heaps[0].size, 8 << 30);
+    }
+
+    #[test]
+    fn builder_keeps_the_last_override() {
+        let properties = DevicePropertiesBuilder::new()
+            .override_max_allocation_count(64)
+            .override_max_allocation_count(128)
+            .apply(queried_properties());
+
+        assert_eq!(properties.max_memory_allocation_count, 128);
     }
-    if props.contains(MemoryPropertyFlags::LAZILY_ALLOCATED) {
-        result |= vk::MemoryPropertyFlags::LAZILY_ALLOCATED;
+
+    #[test]
+    fn smallest_heap_size_caps_to_the_smallest_heap() {
+        let mut memory_properties = vk::PhysicalDeviceMemoryProperties {
+            memory_heap_count: 3,
+            ..Default::default()
+        };
+        for (memory_heap, size) in
+            memory_properties
+                .memory_heaps
+                .iter_mut()
+                .zip([8 << 30, 256 << 20, 16 << 30, 1 << 20])
+        {
+            memory_heap.size = size;
+        }
+
+        // The last heap is past `memory_heap_count`
+        assert_eq!(smallest_heap_size(&memory_properties), 256 << 20);
+    }
+
+    #[test]
+    fn plan_without_extensions_caps_allocation_size() {
+        for support in [
+            ExtensionSupport::default(),
+            ExtensionSupport {
+                properties2: true,
+                ..Default::default()
+            },
+            ExtensionSupport {
+                maintenance3: true,
+                ..Default::default()
+            },
+        ] {
+            let plan = QueryPlan::new(vk::make_version(1, 0, 0), support, false);
+            assert!(!plan.query_props, "{support:?}");
+        }
+
+        let with_maintenance3 = ExtensionSupport {
+            properties2: true,
+            maintenance3: true,
+            buffer_device_address: false,
+        };
+        assert!(QueryPlan::new(vk::make_version(1, 0, 0), with_maintenance3, false).query_props);
+        assert!(
+            QueryPlan::new(
+                vk::make_version(1, 1, 0),
+                ExtensionSupport::default(),
+                false
+            )
+            .query_props
+        );
+    }
+
+    #[test]
+    fn query_info_reports_used_paths() {
+        let info = |minor, support, promoted_properties, features_supplied| {
+            QueryPlan::new(vk::make_version(1, minor, 0), support, promoted_properties)
+                .query_info(features_supplied)
+        };
+        let all = ExtensionSupport {
+            properties2: true,
+            maintenance3: true,
+            buffer_device_address: true,
+        };
+
+        assert_eq!(
+            info(0, ExtensionSupport::default(), false, false),
+            PropertiesQueryInfo::default()
+        );
+        assert_eq!(
+            info(0, all, false, false),
+            PropertiesQueryInfo {
+                used_properties2: true,
+                used_maintenance3: true,
+                used_features2: true,
+                buffer_device_address_queried: true,
+            }
+        );
+        assert_eq!(
+            info(2, ExtensionSupport::default(), true, false),
+            PropertiesQueryInfo {
+                used_properties2: true,
+                used_maintenance3: false,
+                used_features2: true,
+                buffer_device_address_queried: true,
+            }
+        );
+        // Promoted properties are only used since 1.2
+        assert!(info(1, all, true, false).used_maintenance3);
+    }
+
+    #[test]
+    fn query_info_with_supplied_features_skips_features2() {
+        let info = QueryPlan::new(
+            vk::make_version(1, 2, 0),
+            ExtensionSupport::default(),
+            false,
+        )
+        .query_info(true);
+
+        assert!(!info.used_features2);
+        assert!(info.buffer_device_address_queried);
+    }
+
+    #[test]
+    fn check_version_rejects_higher_versions() {
+        let api_version = vk::make_version(1, 1, 0);
+
+        assert!(matches!(
+            check_version(vk::make_version(1, 2, 0), api_version),
+            Err(DevicePropertiesError::VersionTooHigh {
+                requested,
+                instance,
+            }) if requested == vk::make_version(1, 2, 0) && instance == api_version
+        ));
+        assert!(check_version(vk::make_version(2, 0, 0), api_version).is_err());
+    }
+
+    #[test]
+    fn check_version_ignores_patch_versions() {
+        let api_version = vk::make_version(1, 1, 0);
+
+        assert!(check_version(vk::make_version(1, 0, 0), api_version).is_ok());
+        assert!(check_version(vk::make_version(1, 1, 0), api_version).is_ok());
+        assert!(check_version(vk::make_version(1, 1, 200), api_version).is_ok());
+    }
+
+    #[test]
+    fn plan_for_each_extension_support() {
+        for bits in 0..8 {
+            let support = ExtensionSupport {
+                properties2: bits & 1 != 0,
+                maintenance3: bits & 2 != 0,
+                buffer_device_address: bits & 4 != 0,
+            };
+            let plan = |minor| {
+                let plan = QueryPlan::new(vk::make_version(1, minor, 0), support, false);
+                (plan.properties2, plan.query_props, plan.query_features)
+            };
+
+            assert_eq!(
+                plan(0),
+                (
+                    support.properties2,
+                    support.properties2 && support.maintenance3,
+                    support.properties2 && support.buffer_device_address,
+                ),
+                "{support:?}"
+            );
+            // Flags of functionality promoted to core are ignored
+            assert_eq!(
+                plan(1),
+                (true, true, support.buffer_device_address),
+                "{support:?}"
+            );
+            assert_eq!(plan(2), (true, true, true), "{support:?}");
+            assert_eq!(plan(3), (true, true, true), "{support:?}");
+        }
+    }
+
+    #[test]
+    fn query_info_reports_skipped_feature_query() {
+        let skipped = |minor, support| {
+            !QueryPlan::new(vk::make_version(1, minor, 0), support, false)
+                .query_info(false)
+                .buffer_device_address_queried
+        };
+        let extension_only = ExtensionSupport {
+            buffer_device_address: true,
+            ..Default::default()
+        };
+
+        // "VK_KHR_get_physical_device_properties2" is required on 1.0
+        assert!(skipped(0, ExtensionSupport::default()));
+        assert!(skipped(0, extension_only));
+        assert!(skipped(1, ExtensionSupport::default()));
+        assert!(!skipped(1, extension_only));
+        assert!(!skipped(2, ExtensionSupport::default()));
+    }
+
+    #[test]
+    fn smallest_heap_size_without_heaps_is_unlimited() {
+        let memory_properties = vk::PhysicalDeviceMemoryProperties::default();
+
+        assert_eq!(smallest_heap_size(&memory_properties), u64::MAX);
     }
-    result
 }
@@ -0,0 +1,486 @@
+use std::ptr::NonNull;
+
+use gpu_alloc_types::{DeviceMapError, MemoryDevice};
+use vulkanalia::prelude::v1_0::*;
+use vulkanalia::vk::KhrMapMemory2ExtensionDeviceCommands;
+
+use crate::{map_error, VulkanaliaMemoryDevice, VulkanaliaMemoryError};
+
+/// Mapped region of a memory object, unmapped on drop.
+///
+/// Created with [`VulkanaliaMemoryDevice::map_memory_scoped`] or [`map`](Self::map)
+/// with any memory device, e.g. `MockMemoryDevice` with its `u64` memory handles as `M`.
+///
+/// While the guard is alive the memory object must not be mapped again,
+/// freed or unmapped through other means.
+pub struct MappedMemory<'a, D = VulkanaliaMemoryDevice, M = vk::DeviceMemory>
+where
+    D: MemoryDevice<M> + ?Sized,
+    M: Copy,
+{
+    device: &'a D,
+    memory: M,
+    ptr: NonNull<u8>,
+    offset: u64,
+    len: usize,
+}
+
+impl<'a, D, M> MappedMemory<'a, D, M>
+where
+    D: MemoryDevice<M> + ?Sized,
+    M: Copy,
+{
+    /// Maps region of the memory object, returning a guard which unmaps it on drop.
+    ///
+    /// The guard needs to know the mapped length, so `vk::WHOLE_SIZE` is rejected
+    /// with [`DeviceMapError::MapFailed`] without mapping anything.
+    /// Pass `allocation_size - offset` to map the rest of the memory object.
+    ///
+    /// # Safety
+    ///
+    /// Same as for [`MemoryDevice::map_memory`].
+    pub unsafe fn map(
+        device: &'a D,
+        mut memory: M,
+        offset: u64,
+        size: u64,
+    ) -> Result<Self, DeviceMapError> {
+        if size == vk::WHOLE_SIZE {
+            return Err(DeviceMapError::MapFailed);
+        }
+        let len = usize::try_from(size).map_err(|_| DeviceMapError::OutOfHostMemory)?;
+
+        let ptr = device.map_memory(&mut memory, offset, size)?;
+        Ok(MappedMemory {
+            device,
+            memory,
+            ptr,
+            offset,
+            len,
+        })
+    }
+
+    /// Returns pointer to the start of the mapped region.
+    pub fn as_ptr(&self) -> *mut u8 {
+        self.ptr.as_ptr()
+    }
+
+    /// Returns the length of the mapped region in bytes.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns `true` if the mapped region is empty.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns the mapped memory object.
+    pub fn memory(&self) -> M {
+        self.memory
+    }
+
+    /// Returns the offset of the mapped region from the start of the memory object.
+    pub fn offset(&self) -> u64 {
+        self.offset
+    }
+
+    /// Returns the mapped region as a byte slice.
+    ///
+    /// # Safety
+    ///
+    /// - The device must not write to the region while the slice is alive.
+    /// - For non-coherent memory the region must be invalidated to observe device writes.
+    pub unsafe fn as_slice(&self) -> &[u8] {
+        std::slice::from_raw_parts(self.ptr.as_ptr(), self.len)
+    }
+
+    /// Returns the mapped region as a mutable byte slice.
+    ///
+    /// # Safety
+    ///
+    /// - The device must not access the region while the slice is alive.
+    /// - For non-coherent memory the region must be flushed to make host writes
+    ///   visible to the device.
+    pub unsafe fn as_mut_slice(&mut self) -> &mut [u8] {
+        std::slice::from_raw_parts_mut(self.ptr.as_ptr(), self.len)
+    }
+
+    /// Copies `value` into the mapped region at `offset` bytes from its start.
+    ///
+    /// Returns [`VulkanaliaMemoryError::OutOfBounds`] without writing anything
+    /// when the value does not fit into the mapped region.
+    /// The value is copied bytewise, so `offset` needs no alignment for the host,
+    /// but it must follow the layout expected by the device.
+    ///
+    /// # Safety
+    ///
+    /// Same as for [`as_mut_slice`](Self::as_mut_slice).
+    pub unsafe fn write_at<T: Copy>(
+        &mut self,
+        offset: usize,
+        value: &T,
+    ) -> Result<(), VulkanaliaMemoryError> {
+        self.write_slice_at(offset, std::slice::from_ref(value))
+    }
+
+    /// Copies `data` into the mapped region at `offset` bytes from its start.
+    ///
+    /// Returns [`VulkanaliaMemoryError::OutOfBounds`] without writing anything
+    /// when the data does not fit into the mapped region.
+    /// The data is copied bytewise, so `offset` needs no alignment for the host,
+    /// but it must follow the layout expected by the device.
+    ///
+    /// # Safety
+    ///
+    /// Same as for [`as_mut_slice`](Self::as_mut_slice).
+    pub unsafe fn write_slice_at<T: Copy>(
+        &mut self,
+        offset: usize,
+        data: &[T],
+    ) -> Result<(), VulkanaliaMemoryError> {
+        let size = std::mem::size_of_val(data);
+        match offset.checked_add(size) {
+            Some(end) if end <= self.len => {}
+            _ => return Err(VulkanaliaMemoryError::OutOfBounds),
+        }
+
+        std::ptr::copy_nonoverlapping(
+            data.as_ptr().cast::<u8>(),
+            self.ptr.as_ptr().add(offset),
+            size,
+        );
+        Ok(())
+    }
+}
+
+impl<D, M> Drop for MappedMemory<'_, D, M>
+where
+    D: MemoryDevice<M> + ?Sized,
+    M: Copy,
+{
+    fn drop(&mut self) {
+        unsafe {
+            // SAFETY: The memory object was mapped when the guard was created
+            // and it is still owned by the guard's mapping.
+            self.device.unmap_memory(&mut self.memory);
+        }
+    }
+}
+
+/// Returns `true` if `[offset, offset + size)` is a valid range to map from a memory object
+/// of `allocation_size` bytes.
+///
+/// The range must not be empty and must not overflow.
+/// With `vk::WHOLE_SIZE` as the size only `offset` needs to be within the memory object.
+pub(crate) fn range_in_bounds(offset: u64, size: u64, allocation_size: u64) -> bool {
+    if size == vk::WHOLE_SIZE {
+        offset < allocation_size
+    } else {
+        size != 0
+            && offset
+                .checked_add(size)
+                .map_or(false, |end| end <= allocation_size)
+    }
+}
+
+/// Builds `VkMemoryMapInfoKHR` with `VkMemoryMapPlacedInfoEXT` chained into it
+/// and passes it to `f`, the structures are only valid during the call.
+fn with_placed_map_info<R>(
+    memory: vk::DeviceMemory,
+    offset: u64,
+    size: u64,
+    placed_address: NonNull<u8>,
+    f: impl FnOnce(&vk::MemoryMapInfo) -> R,
+) -> R {
+    let mut placed_info = vk::MemoryMapPlacedInfoEXT {
+        placed_address: placed_address.as_ptr().cast(),
+        ..Default::default()
+    };
+    let info = vk::MemoryMapInfo::builder()
+        .flags(vk::MemoryMapFlags::PLACED_EXT)
+        .memory(memory)
+        .offset(offset)
+        .size(size)
+        .push_next(&mut placed_info);
+
+    f(&info)
+}
+
+impl VulkanaliaMemoryDevice {
+    /// Maps memory object from `offset` to its end, passing `vk::WHOLE_SIZE` as the size.
+    ///
+    /// The mapped length is `allocation_size - offset`. The caller still needs
+    /// to know the size of the memory object to access the mapping safely.
+    ///
+    /// # Safety
+    ///
+    /// Same as for [`MemoryDevice::map_memory`], with `size` being `vk::WHOLE_SIZE`.
+    pub unsafe fn map_whole_memory(
+        &self,
+        memory: &mut vk::DeviceMemory,
+        offset: u64,
+    ) -> Result<NonNull<u8>, DeviceMapError> {
+        self.map_memory(memory, offset, vk::WHOLE_SIZE)
+    }
+
+    /// Maps region of the memory object like [`MemoryDevice::map_memory`] does,
+    /// but checks it against `allocation_size` first.
+    ///
+    /// Returns [`VulkanaliaMemoryError::OutOfBounds`] without calling the driver
+    /// when `offset + size` exceeds `allocation_size` or overflows, or `size` is zero.
+    /// With `vk::WHOLE_SIZE` as the size only `offset` needs to be within the memory object.
+    ///
+    /// # Safety
+    ///
+    /// Same as for [`MemoryDevice::map_memory`].
+    /// `allocation_size` must be the size of `memory`.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(target = "gpu_alloc::vulkanalia", skip(self))
+    )]
+    pub unsafe fn map_memory_checked(
+        &self,
+        memory: &mut vk::DeviceMemory,
+        allocation_size: u64,
+        offset: u64,
+        size: u64,
+    ) -> Result<NonNull<u8>, VulkanaliaMemoryError> {
+        if !range_in_bounds(offset, size, allocation_size) {
+            return Err(VulkanaliaMemoryError::OutOfBounds);
+        }
+
+        let ptr = self
+            .device
+            .map_memory(*memory, offset, size, vk::MemoryMapFlags::empty())
+            .map_err(VulkanaliaMemoryError::from)?;
+
+        self.non_null_mapping(*memory, ptr)
+            .ok_or(VulkanaliaMemoryError::MapFailed)
+    }
+
+    /// Maps memory object at the specified host virtual address.
+    ///
+    /// Calls `vkMapMemory2KHR` with `VK_MEMORY_MAP_PLACED_BIT_EXT` flag and
+    /// `VkMemoryMapPlacedInfoEXT` chained into the map info.
+    /// A placement rejected by the driver is reported as [`DeviceMapError::MapFailed`].
+    /// The mapping is released with [`MemoryDevice::unmap_memory`] as usual.
+    ///
+    /// # Safety
+    ///
+    /// Same as for [`MemoryDevice::map_memory`]. In addition:
+    /// - Extensions "VK_KHR_map_memory2" and "VK_EXT_map_memory_placed" must be enabled
+    ///   along with the `memoryMapPlaced` feature.
+    /// - `placed_address` must be aligned to
+    ///   `PhysicalDeviceMapMemoryPlacedPropertiesEXT::min_placed_memory_map_alignment`.
+    /// - Without the `memoryMapRangePlaced` feature, `offset` must be zero and
+    ///   `size` must be `vk::WHOLE_SIZE` or the size of the memory object.
+    ///   Otherwise `offset` must be aligned to `min_placed_memory_map_alignment` too.
+    /// - The address range must be reserved by the application and must not be used
+    ///   for anything else while mapped.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(target = "gpu_alloc::vulkanalia", skip(self))
+    )]
+    pub unsafe fn map_memory_placed(
+        &self,
+        memory: &mut vk::DeviceMemory,
+        offset: u64,
+        size: u64,
+        placed_address: NonNull<u8>,
+    ) -> Result<NonNull<u8>, DeviceMapError> {
+        let result = with_placed_map_info(*memory, offset, size, placed_address, |info| {
+            self.device.map_memory2_khr(info)
+        });
+
+        match result {
+            Ok(ptr) => {
+                #[cfg(feature = "tracing")]
+                tracing::trace!(target: "gpu_alloc::vulkanalia", ?ptr, "Memory mapped");
+                #[cfg(feature = "log")]
+                log::trace!(target: "gpu_alloc::vulkanalia", "Memory mapped at {ptr:?}");
+
+                self.non_null_mapping(*memory, ptr)
+                    .ok_or(DeviceMapError::MapFailed)
+            }
+            Err(e) => Err(map_error(e)),
+        }
+    }
+
+    /// Maps region of the memory object, returning a guard which unmaps it on drop.
+    ///
+    /// Same as [`MappedMemory::map`] with this device.
+    ///
+    /// # Safety
+    ///
+    /// Same as for [`MemoryDevice::map_memory`].
+    pub unsafe fn map_memory_scoped(
+        &self,
+        memory: vk::DeviceMemory,
+        offset: u64,
+        size: u64,
+    ) -> Result<MappedMemory<'_>, DeviceMapError> {
+        MappedMemory::map(self, memory, offset, size)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(feature = "mock")]
+    use gpu_alloc_types::AllocationFlags;
+
+    use super::*;
+    use crate::allocate::{chain_types, find_in_chain};
+    #[cfg(feature = "mock")]
+    use crate::MockMemoryDevice;
+
+    #[test]
+    fn range_in_bounds_checks_the_end() {
+        assert!(range_in_bounds(0, 16, 16));
+        assert!(range_in_bounds(8, 8, 16));
+        assert!(!range_in_bounds(8, 9, 16));
+        assert!(!range_in_bounds(16, 1, 16));
+    }
+
+    #[test]
+    fn range_in_bounds_rejects_empty_and_overflowing_ranges() {
+        assert!(!range_in_bounds(0, 0, 16));
+        assert!(!range_in_bounds(16, 0, 16));
+        assert!(range_in_bounds(1, u64::MAX - 1, u64::MAX));
+        assert!(!range_in_bounds(2, u64::MAX - 1, u64::MAX));
+        assert!(!range_in_bounds(u64::MAX, 2, u64::MAX));
+    }
+
+    #[test]
+    fn range_in_bounds_maps_whole_size_to_the_end() {
+        assert!(range_in_bounds(0, vk::WHOLE_SIZE, 16));
+        assert!(range_in_bounds(15, vk::WHOLE_SIZE, 16));
+        assert!(!range_in_bounds(16, vk::WHOLE_SIZE, 16));
+        assert!(!range_in_bounds(0, vk::WHOLE_SIZE, 0));
+    }
+
+    #[test]
+    fn placed_map_info_chains_placed_address() {
+        let memory = vk::DeviceMemory::from_raw(3);
+        let mut reserved = [0u8; 16];
+        let address = NonNull::from(&mut reserved).cast::<u8>();
+
+        let (info, types, placed) =
+            with_placed_map_info(memory, 0, vk::WHOLE_SIZE, address, |info| {
+                (
+                    (info.flags, info.memory, info.offset, info.size),
+                    chain_types(info),
+                    find_in_chain::<vk::MemoryMapPlacedInfoEXT>(info),
+                )
+            });
+
+        assert_eq!(
+            info,
+            (vk::MemoryMapFlags::PLACED_EXT, memory, 0, vk::WHOLE_SIZE)
+        );
+        assert_eq!(types, [vk::StructureType::MEMORY_MAP_PLACED_INFO_EXT]);
+        assert_eq!(placed.unwrap().placed_address, address.as_ptr().cast());
+    }
+
+    #[cfg(feature = "mock")]
+    fn allocate(device: &MockMemoryDevice, size: u64) -> u64 {
+        unsafe { device.allocate_memory(size, 0, AllocationFlags::empty()) }.unwrap()
+    }
+
+    #[cfg(feature = "mock")]
+    #[test]
+    fn drop_unmaps_memory() {
+        let device = MockMemoryDevice::new();
+        let memory = allocate(&device, 64);
+
+        let mapped = unsafe { MappedMemory::map(&device, memory, 16, 32) }.unwrap();
+        assert_eq!(
+            (mapped.memory(), mapped.offset(), mapped.len()),
+            (memory, 16, 32)
+        );
+        assert_eq!(device.calls().unmap, 0);
+        drop(mapped);
+
+        assert_eq!(device.calls().unmap, 1);
+        // Mapping again would panic if the memory object was still mapped
+        drop(unsafe { MappedMemory::map(&device, memory, 0, 64) }.unwrap());
+        unsafe { device.deallocate_memory(memory) };
+    }
+
+    #[cfg(feature = "mock")]
+    #[test]
+    fn whole_size_is_rejected_without_mapping() {
+        let device = MockMemoryDevice::new();
+        let memory = allocate(&device, 64);
+
+        let result = unsafe { MappedMemory::map(&device, memory, 0, vk::WHOLE_SIZE) };
+
+        assert!(matches!(result, Err(DeviceMapError::MapFailed)));
+        assert_eq!(device.calls().map, 0);
+        unsafe { device.deallocate_memory(memory) };
+    }
+
+    #[cfg(feature = "mock")]
+    #[test]
+    fn writes_within_the_region() {
+        let device = MockMemoryDevice::new();
+        let memory = allocate(&device, 16);
+        let mut mapped = unsafe { MappedMemory::map(&device, memory, 0, 16) }.unwrap();
+
+        unsafe {
+            mapped.write_at(0, &0x0403_0201u32).unwrap();
+            mapped.write_slice_at(12, &[5u8, 6, 7, 8]).unwrap();
+            assert_eq!(&mapped.as_slice()[..4], [1, 2, 3, 4]);
+            assert_eq!(&mapped.as_slice()[12..], [5, 6, 7, 8]);
+        }
+        drop(mapped);
+        unsafe { device.deallocate_memory(memory) };
+    }
+
+    #[cfg(feature = "mock")]
+    #[test]
+    fn out_of_bounds_writes_are_rejected() {
+        let device = MockMemoryDevice::new();
+        let memory = allocate(&device, 16);
+        let mut mapped = unsafe { MappedMemory::map(&device, memory, 0, 16) }.unwrap();
+
+        unsafe {
+            assert!(matches!(
+                mapped.write_at(13, &0u32),
+                Err(VulkanaliaMemoryError::OutOfBounds)
+            ));
+            assert!(matches!(
+                mapped.write_slice_at(16, &[0u8]),
+                Err(VulkanaliaMemoryError::OutOfBounds)
+            ));
+            assert!(matches!(
+                mapped.write_slice_at(usize::MAX, &[0u8, 0]),
+                Err(VulkanaliaMemoryError::OutOfBounds)
+            ));
+            assert!(mapped.as_slice().iter().all(|&byte| byte == 0));
+            mapped.write_slice_at::<u8>(16, &[]).unwrap();
+        }
+        drop(mapped);
+        unsafe { device.deallocate_memory(memory) };
+    }
+
+    #[cfg(feature = "mock")]
+    #[test]
+    fn whole_size_maps_to_the_end_of_memory() {
+        let device = MockMemoryDevice::new();
+        let mut memory = allocate(&device, 64);
+
+        unsafe {
+            let ptr = device.map_memory(&mut memory, 48, vk::WHOLE_SIZE).unwrap();
+            // The last byte of the memory object is within the mapping
+            ptr.as_ptr().add(15).write(0xff);
+            device.unmap_memory(&mut memory);
+
+            assert!(matches!(
+                device.map_memory(&mut memory, 64, vk::WHOLE_SIZE),
+                Err(DeviceMapError::MapFailed)
+            ));
+            device.deallocate_memory(memory);
+        }
+    }
+}
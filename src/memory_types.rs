@@ -0,0 +1,638 @@
+use gpu_alloc_types::{DeviceProperties, MemoryHeap, MemoryPropertyFlags, MemoryType};
+#[cfg(feature = "smallvec")]
+use smallvec::SmallVec;
+use vulkanalia::prelude::v1_0::*;
+
+/// Returns a bitmask of memory types carrying `DEVICE_COHERENT_AMD`
+/// or `DEVICE_UNCACHED_AMD` property flags.
+///
+/// Bit `i` is set when memory type `i` has either flag,
+/// the same encoding as `VkMemoryRequirements::memoryTypeBits`.
+///
+/// These flags have no `gpu-alloc-types` counterpart, so [`memory_properties_from`]
+/// drops them and such types look like regular ones in `DeviceProperties`.
+/// That is why the memory properties are queried from the `instance` again
+/// instead of taking `DeviceProperties`, see [`amd_coherent_memory_types_from`]
+/// to reuse already queried ones.
+/// Use this mask to keep uncached memory away from performance-critical paths.
+///
+/// # Safety
+///
+/// `physical_device` must be queried from an [`Instance`] associated with this `instance`.
+///
+/// [`memory_properties_from`]: crate::memory_properties_from
+pub unsafe fn amd_coherent_memory_types(
+    instance: &Instance,
+    physical_device: vk::PhysicalDevice,
+) -> u32 {
+    let memory_properties = instance.get_physical_device_memory_properties(physical_device);
+    amd_coherent_memory_types_from(&memory_properties)
+}
+
+/// Returns a bitmask of memory types carrying `DEVICE_COHERENT_AMD`
+/// or `DEVICE_UNCACHED_AMD` property flags, see [`amd_coherent_memory_types`].
+pub fn amd_coherent_memory_types_from(
+    memory_properties: &vk::PhysicalDeviceMemoryProperties,
+) -> u32 {
+    let count = (memory_properties.memory_type_count as usize).min(vk::MAX_MEMORY_TYPES);
+
+    memory_properties.memory_types[..count]
+        .iter()
+        .enumerate()
+        .filter(|(_, memory_type)| {
+            memory_type.property_flags.intersects(
+                vk::MemoryPropertyFlags::DEVICE_COHERENT_AMD
+                    | vk::MemoryPropertyFlags::DEVICE_UNCACHED_AMD,
+            )
+        })
+        .fold(0, |mask, (index, _)| mask | (1 << index))
+}
+
+/// Finds index of a memory type allowed by `type_bits` for manual allocations.
+///
+/// Memory types are checked in order of their indices:
+/// - First one having both `required` and `preferred` flags is returned.
+/// - Otherwise first one having `required` flags is returned.
+///
+/// `type_bits` is a bitmask as in `VkMemoryRequirements::memoryTypeBits`.
+pub fn find_memory_type(
+    props: &DeviceProperties<'_>,
+    type_bits: u32,
+    required: MemoryPropertyFlags,
+    preferred: MemoryPropertyFlags,
+) -> Option<u32> {
+    let find = |flags: MemoryPropertyFlags| {
+        props
+            .memory_types
+            .iter()
+            .enumerate()
+            .take(32)
+            .find(|(index, memory_type)| {
+                type_bits & (1 << index) != 0 && memory_type.props.contains(flags)
+            })
+            .map(|(index, _)| index as u32)
+    };
+
+    find(required | preferred).or_else(|| find(required))
+}
+
+/// Returns property flags of the memory type.
+///
+/// Returns empty flags when `memory_type` is out of range.
+pub fn memory_type_props(props: &DeviceProperties<'_>, memory_type: u32) -> MemoryPropertyFlags {
+    props
+        .memory_types
+        .get(memory_type as usize)
+        .map_or(MemoryPropertyFlags::empty(), |ty| ty.props)
+}
+
+/// Returns the heap backing the memory type.
+///
+/// Returns `None` when `memory_type` or its heap index is out of range.
+pub fn memory_type_heap<'a>(
+    props: &'a DeviceProperties<'_>,
+    memory_type: u32,
+) -> Option<&'a MemoryHeap> {
+    let memory_type = props.memory_types.get(memory_type as usize)?;
+    props.memory_heaps.get(memory_type.heap as usize)
+}
+
+/// Returns the size in bytes of the heap backing the memory type,
+/// see [`memory_type_heap`].
+pub fn memory_type_heap_size(props: &DeviceProperties<'_>, memory_type: u32) -> Option<u64> {
+    memory_type_heap(props, memory_type).map(|heap| heap.size)
+}
+
+/// Intended access pattern of memory, see [`memory_type_cost`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum AccessIntent {
+    /// Only the device accesses the memory.
+    DeviceOnly,
+    /// The host writes the memory and the device reads it.
+    Upload,
+    /// The device writes the memory and the host reads it.
+    Download,
+}
+
+/// Cost of memory types unusable for an intent.
+const UNUSABLE_COST: u32 = u32::MAX;
+
+/// Returns a heuristic cost of using the memory type for `intent`, lower is better.
+///
+/// The cost is the sum of penalties for the flags of the memory type:
+///
+/// | Intent       | Condition                  | Penalty    |
+/// |--------------|----------------------------|------------|
+/// | any          | `PROTECTED`                | 16         |
+/// | `DeviceOnly` | no `DEVICE_LOCAL`          | 4          |
+/// | `DeviceOnly` | `HOST_VISIBLE`             | 1          |
+/// | `DeviceOnly` | `LAZILY_ALLOCATED`         | 8          |
+/// | `Upload`     | no `HOST_VISIBLE`          | unusable   |
+/// | `Upload`     | no `DEVICE_LOCAL`          | 2          |
+/// | `Upload`     | no `HOST_COHERENT`         | 1          |
+/// | `Upload`     | `HOST_CACHED`              | 1          |
+/// | `Download`   | no `HOST_VISIBLE`          | unusable   |
+/// | `Download`   | no `HOST_CACHED`           | 4          |
+/// | `Download`   | no `HOST_COHERENT`         | 1          |
+///
+/// Unusable memory types and out of range indices cost `u32::MAX`.
+/// As a result device local host visible memory is preferred for uploads
+/// and host cached memory for readback.
+pub fn memory_type_cost(
+    props: &DeviceProperties<'_>,
+    memory_type: u32,
+    intent: AccessIntent,
+) -> u32 {
+    let Some(memory_type) = props.memory_types.get(memory_type as usize) else {
+        return UNUSABLE_COST;
+    };
+    let flags = memory_type.props;
+    let penalty = |condition: bool, penalty: u32| if condition { penalty } else { 0 };
+
+    let protected = penalty(flags.contains(MemoryPropertyFlags::PROTECTED), 16);
+    let host_visible = flags.contains(MemoryPropertyFlags::HOST_VISIBLE);
+    let device_local = flags.contains(MemoryPropertyFlags::DEVICE_LOCAL);
+    let host_coherent = flags.contains(MemoryPropertyFlags::HOST_COHERENT);
+    let host_cached = flags.contains(MemoryPropertyFlags::HOST_CACHED);
+
+    match intent {
+        AccessIntent::DeviceOnly => {
+            protected
+                + penalty(!device_local, 4)
+                + penalty(host_visible, 1)
+                + penalty(flags.contains(MemoryPropertyFlags::LAZILY_ALLOCATED), 8)
+        }
+        AccessIntent::Upload if host_visible => {
+            protected
+                + penalty(!device_local, 2)
+                + penalty(!host_coherent, 1)
+                + penalty(host_cached, 1)
+        }
+        AccessIntent::Download if host_visible => {
+            protected + penalty(!host_cached, 4) + penalty(!host_coherent, 1)
+        }
+        AccessIntent::Upload | AccessIntent::Download => UNUSABLE_COST,
+    }
+}
+
+/// Iterates over memory types having all of the `required` flags,
+/// yielding their indices along with the types in order of indices.
+pub fn memory_types_matching<'a>(
+    props: &'a DeviceProperties<'_>,
+    required: MemoryPropertyFlags,
+) -> impl Iterator<Item = (u32, &'a MemoryType)> + 'a {
+    props
+        .memory_types
+        .iter()
+        .enumerate()
+        .filter(move |(_, memory_type)| memory_type.props.contains(required))
+        .map(|(index, memory_type)| (index as u32, memory_type))
+}
+
+/// Returns indices of memory types which are both device local and host visible,
+/// ordered by the size of their heap, largest first.
+///
+/// With Resizable BAR such memory types cover the whole video memory
+/// and are ideal for uploads written directly by the host.
+/// Without it they are usually backed by a small 256 MiB heap, or absent.
+///
+/// Requires `smallvec` feature.
+#[cfg(feature = "smallvec")]
+pub fn bar_memory_types(props: &DeviceProperties<'_>) -> SmallVec<[u32; 4]> {
+    let heap_size = |memory_type: u32| {
+        let heap = props.memory_types[memory_type as usize].heap;
+        props
+            .memory_heaps
+            .get(heap as usize)
+            .map_or(0, |heap| heap.size)
+    };
+
+    let mut result = props
+        .memory_types
+        .iter()
+        .enumerate()
+        .filter(|(_, memory_type)| {
+            memory_type
+                .props
+                .contains(MemoryPropertyFlags::DEVICE_LOCAL | MemoryPropertyFlags::HOST_VISIBLE)
+        })
+        .map(|(index, _)| index as u32)
+        .collect::<SmallVec<[u32; 4]>>();
+
+    result.sort_by_key(|&memory_type| std::cmp::Reverse(heap_size(memory_type)));
+    result
+}
+
+/// Returns `true` if the memory type is host coherent,
+/// so mapped ranges of its memory need no flushes and invalidations.
+///
+/// Returns `false` when `memory_type` is out of bounds.
+pub fn is_host_coherent(props: &DeviceProperties<'_>, memory_type: u32) -> bool {
+    memory_type_props(props, memory_type).contains(MemoryPropertyFlags::HOST_COHERENT)
+}
+
+/// Returns `true` if mapped ranges of memory of this type must be flushed
+/// and invalidated explicitly, the opposite of [`is_host_coherent`].
+pub fn requires_flush(props: &DeviceProperties<'_>, memory_type: u32) -> bool {
+    !is_host_coherent(props, memory_type)
+}
+
+/// Properties of a memory heap not represented in `gpu-alloc-types`' `MemoryHeap`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct HeapFlags {
+    /// Heap corresponds to device-local memory.
+    pub device_local: bool,
+    /// In a logical device representing more than one physical device,
+    /// there is a per-physical device instance of the heap memory.
+    pub multi_instance: bool,
+}
+
+/// Maps `vulkanalia`'s `MemoryHeapFlags` to [`HeapFlags`].
+pub fn heap_flags_from(flags: vk::MemoryHeapFlags) -> HeapFlags {
+    HeapFlags {
+        device_local: flags.contains(vk::MemoryHeapFlags::DEVICE_LOCAL),
+        multi_instance: flags.contains(vk::MemoryHeapFlags::MULTI_INSTANCE),
+    }
+}
+
+/// Returns flags of each memory heap of the physical device,
+/// indexed the same way as `DeviceProperties::memory_heaps`.
+///
+/// Requires `smallvec` feature.
+///
+/// # Safety
+///
+/// `physical_device` must be queried from an [`Instance`] associated with this `instance`.
+#[cfg(feature = "smallvec")]
+pub unsafe fn memory_heap_flags(
+    instance: &Instance,
+    physical_device: vk::PhysicalDevice,
+) -> SmallVec<[vk::MemoryHeapFlags; vk::MAX_MEMORY_HEAPS]> {
+    let memory_properties = instance.get_physical_device_memory_properties(physical_device);
+    memory_heap_flags_from(&memory_properties)
+}
+
+/// Returns flags of each memory heap, see [`memory_heap_flags`].
+///
+/// Requires `smallvec` feature.
+#[cfg(feature = "smallvec")]
+pub fn memory_heap_flags_from(
+    memory_properties: &vk::PhysicalDeviceMemoryProperties,
+) -> SmallVec<[vk::MemoryHeapFlags; vk::MAX_MEMORY_HEAPS]> {
+    let count = (memory_properties.memory_heap_count as usize).min(vk::MAX_MEMORY_HEAPS);
+
+    memory_properties.memory_heaps[..count]
+        .iter()
+        .map(|memory_heap| memory_heap.flags)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::borrow::Cow;
+
+    use super::*;
+
+    /// Typical discrete GPU: device local memory, host memory with and without
+    /// caching, and a small BAR heap.
+    fn discrete_gpu() -> DeviceProperties<'static> {
+        let memory_type = |props, heap| MemoryType { props, heap };
+        DeviceProperties {
+            memory_types: Cow::Owned(vec![
+                memory_type(MemoryPropertyFlags::DEVICE_LOCAL, 0),
+                memory_type(
+                    MemoryPropertyFlags::HOST_VISIBLE | MemoryPropertyFlags::HOST_COHERENT,
+                    1,
+                ),
+                memory_type(
+                    MemoryPropertyFlags::HOST_VISIBLE
+                        | MemoryPropertyFlags::HOST_COHERENT
+                        | MemoryPropertyFlags::HOST_CACHED,
+                    1,
+                ),
+                memory_type(
+                    MemoryPropertyFlags::DEVICE_LOCAL
+                        | MemoryPropertyFlags::HOST_VISIBLE
+                        | MemoryPropertyFlags::HOST_COHERENT,
+                    2,
+                ),
+            ]),
+            memory_heaps: Cow::Owned(vec![
+                MemoryHeap { size: 8 << 30 },
+                MemoryHeap { size: 16 << 30 },
+                MemoryHeap { size: 256 << 20 },
+            ]),
+            max_memory_allocation_count: 4096,
+            max_memory_allocation_size: u64::MAX,
+            non_coherent_atom_size: 64,
+            buffer_device_address: false,
+        }
+    }
+
+    #[test]
+    fn find_memory_type_prefers_preferred_flags() {
+        let props = discrete_gpu();
+        let find = |type_bits, required, preferred| {
+            find_memory_type(&props, type_bits, required, preferred)
+        };
+
+        assert_eq!(
+            find(
+                !0,
+                MemoryPropertyFlags::HOST_VISIBLE,
+                MemoryPropertyFlags::empty()
+            ),
+            Some(1)
+        );
+        assert_eq!(
+            find(
+                !0,
+                MemoryPropertyFlags::HOST_VISIBLE,
+                MemoryPropertyFlags::HOST_CACHED
+            ),
+            Some(2)
+        );
+        assert_eq!(
+            find(
+                !0,
+                MemoryPropertyFlags::DEVICE_LOCAL,
+                MemoryPropertyFlags::HOST_VISIBLE
+            ),
+            Some(3)
+        );
+    }
+
+    #[test]
+    fn find_memory_type_falls_back_to_required_flags() {
+        let props = discrete_gpu();
+
+        assert_eq!(
+            find_memory_type(
+                &props,
+                0b1011,
+                MemoryPropertyFlags::HOST_VISIBLE,
+                MemoryPropertyFlags::HOST_CACHED
+            ),
+            Some(1)
+        );
+        assert_eq!(
+            find_memory_type(
+                &props,
+                0b0001,
+                MemoryPropertyFlags::HOST_VISIBLE,
+                MemoryPropertyFlags::empty()
+            ),
+            None
+        );
+        assert_eq!(
+            find_memory_type(
+                &props,
+                0,
+                MemoryPropertyFlags::empty(),
+                MemoryPropertyFlags::empty()
+            ),
+            None
+        );
+    }
+
+    #[test]
+    fn memory_type_cost_scores() {
+        let props = discrete_gpu();
+        let costs = |intent| {
+            (0..5)
+                .map(|memory_type| memory_type_cost(&props, memory_type, intent))
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(costs(AccessIntent::DeviceOnly), [0, 5, 5, 1, u32::MAX]);
+        assert_eq!(costs(AccessIntent::Upload), [u32::MAX, 2, 3, 0, u32::MAX]);
+        assert_eq!(costs(AccessIntent::Download), [u32::MAX, 4, 0, 4, u32::MAX]);
+    }
+
+    #[test]
+    fn memory_type_cost_penalizes_special_memory() {
+        let mut props = discrete_gpu();
+        props.memory_types = Cow::Owned(vec![
+            MemoryType {
+                props: MemoryPropertyFlags::DEVICE_LOCAL | MemoryPropertyFlags::LAZILY_ALLOCATED,
+                heap: 0,
+            },
+            MemoryType {
+                props: MemoryPropertyFlags::DEVICE_LOCAL | MemoryPropertyFlags::PROTECTED,
+                heap: 0,
+            },
+            MemoryType {
+                props: MemoryPropertyFlags::HOST_VISIBLE,
+                heap: 1,
+            },
+        ]);
+
+        assert_eq!(memory_type_cost(&props, 0, AccessIntent::DeviceOnly), 8);
+        assert_eq!(memory_type_cost(&props, 1, AccessIntent::DeviceOnly), 16);
+        assert_eq!(memory_type_cost(&props, 2, AccessIntent::Upload), 3);
+        assert_eq!(memory_type_cost(&props, 2, AccessIntent::Download), 5);
+    }
+
+    #[test]
+    fn memory_type_props_of_out_of_range_type_are_empty() {
+        let props = discrete_gpu();
+        assert_eq!(
+            memory_type_props(&props, 3),
+            MemoryPropertyFlags::DEVICE_LOCAL
+                | MemoryPropertyFlags::HOST_VISIBLE
+                | MemoryPropertyFlags::HOST_COHERENT
+        );
+        assert_eq!(memory_type_props(&props, 4), MemoryPropertyFlags::empty());
+    }
+
+    #[test]
+    fn amd_coherent_memory_types_from_masks_amd_types() {
+        let mut memory_properties = vk::PhysicalDeviceMemoryProperties {
+            memory_type_count: 4,
+            ..Default::default()
+        };
+        for (memory_type, property_flags) in memory_properties.memory_types.iter_mut().zip([
+            vk::MemoryPropertyFlags::DEVICE_LOCAL,
+            vk::MemoryPropertyFlags::DEVICE_LOCAL | vk::MemoryPropertyFlags::DEVICE_COHERENT_AMD,
+            vk::MemoryPropertyFlags::HOST_VISIBLE,
+            vk::MemoryPropertyFlags::DEVICE_COHERENT_AMD
+                | vk::MemoryPropertyFlags::DEVICE_UNCACHED_AMD,
+            // Past `memory_type_count`
+            vk::MemoryPropertyFlags::DEVICE_UNCACHED_AMD,
+        ]) {
+            memory_type.property_flags = property_flags;
+        }
+
+        assert_eq!(amd_coherent_memory_types_from(&memory_properties), 0b1010);
+
+        memory_properties.memory_type_count = 0;
+        assert_eq!(amd_coherent_memory_types_from(&memory_properties), 0);
+    }
+
+    #[test]
+    fn heap_flags_from_plain_and_multi_instance_heaps() {
+        assert_eq!(
+            heap_flags_from(vk::MemoryHeapFlags::empty()),
+            HeapFlags::default()
+        );
+        assert_eq!(
+            heap_flags_from(vk::MemoryHeapFlags::DEVICE_LOCAL),
+            HeapFlags {
+                device_local: true,
+                multi_instance: false,
+            }
+        );
+        assert_eq!(
+            heap_flags_from(
+                vk::MemoryHeapFlags::DEVICE_LOCAL | vk::MemoryHeapFlags::MULTI_INSTANCE
+            ),
+            HeapFlags {
+                device_local: true,
+                multi_instance: true,
+            }
+        );
+    }
+
+    #[cfg(feature = "smallvec")]
+    #[test]
+    fn memory_heap_flags_from_lists_each_heap() {
+        let mut memory_properties = vk::PhysicalDeviceMemoryProperties {
+            memory_heap_count: 2,
+            ..Default::default()
+        };
+        memory_properties.memory_heaps[0].flags =
+            vk::MemoryHeapFlags::DEVICE_LOCAL | vk::MemoryHeapFlags::MULTI_INSTANCE;
+        // Past `memory_heap_count`
+        memory_properties.memory_heaps[2].flags = vk::MemoryHeapFlags::DEVICE_LOCAL;
+
+        assert_eq!(
+            memory_heap_flags_from(&memory_properties).as_slice(),
+            [
+                vk::MemoryHeapFlags::DEVICE_LOCAL | vk::MemoryHeapFlags::MULTI_INSTANCE,
+                vk::MemoryHeapFlags::empty(),
+            ]
+        );
+    }
+
+    #[test]
+    fn host_coherent_types_require_no_flush() {
+        let mut props = discrete_gpu();
+        props.memory_types.to_mut().push(MemoryType {
+            props: MemoryPropertyFlags::HOST_VISIBLE | MemoryPropertyFlags::HOST_CACHED,
+            heap: 1,
+        });
+
+        let coherent = (0..6)
+            .map(|memory_type| is_host_coherent(&props, memory_type))
+            .collect::<Vec<_>>();
+        let flush = (0..6)
+            .map(|memory_type| requires_flush(&props, memory_type))
+            .collect::<Vec<_>>();
+
+        assert_eq!(coherent, [false, true, true, true, false, false]);
+        assert_eq!(flush, [true, false, false, false, true, true]);
+    }
+
+    #[cfg(feature = "smallvec")]
+    #[test]
+    fn bar_memory_types_without_bar() {
+        let mut props = discrete_gpu();
+        props.memory_types.to_mut().truncate(3);
+
+        assert!(bar_memory_types(&props).is_empty());
+    }
+
+    #[cfg(feature = "smallvec")]
+    #[test]
+    fn bar_memory_types_largest_heap_first() {
+        let mut props = discrete_gpu();
+        assert_eq!(bar_memory_types(&props).as_slice(), [3]);
+
+        // Resizable BAR exposes the whole device local heap, in addition to the small one
+        let bar = MemoryPropertyFlags::DEVICE_LOCAL | MemoryPropertyFlags::HOST_VISIBLE;
+        props.memory_types.to_mut().extend([
+            MemoryType {
+                props: bar,
+                heap: 0,
+            },
+            MemoryType {
+                props: bar | MemoryPropertyFlags::HOST_CACHED,
+                heap: 0,
+            },
+        ]);
+
+        assert_eq!(bar_memory_types(&props).as_slice(), [4, 5, 3]);
+    }
+
+    #[test]
+    fn memory_types_matching_yields_matches_in_order() {
+        let props = discrete_gpu();
+        let matching = |required| {
+            memory_types_matching(&props, required)
+                .map(|(index, _)| index)
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(matching(MemoryPropertyFlags::empty()), [0, 1, 2, 3]);
+        assert_eq!(matching(MemoryPropertyFlags::HOST_VISIBLE), [1, 2, 3]);
+        assert_eq!(
+            matching(MemoryPropertyFlags::DEVICE_LOCAL | MemoryPropertyFlags::HOST_VISIBLE),
+            [3]
+        );
+
+        let (index, memory_type) = memory_types_matching(&props, MemoryPropertyFlags::HOST_CACHED)
+            .next()
+            .unwrap();
+        assert_eq!((index, memory_type.heap), (2, 1));
+    }
+
+    #[test]
+    fn memory_types_matching_without_matches_is_empty() {
+        let props = discrete_gpu();
+
+        assert_eq!(
+            memory_types_matching(&props, MemoryPropertyFlags::LAZILY_ALLOCATED).count(),
+            0
+        );
+        assert_eq!(
+            memory_types_matching(
+                &props,
+                MemoryPropertyFlags::DEVICE_LOCAL | MemoryPropertyFlags::HOST_CACHED
+            )
+            .count(),
+            0
+        );
+    }
+
+    #[test]
+    fn memory_type_heap_of_each_type() {
+        let props = discrete_gpu();
+        let sizes = (0..4)
+            .map(|memory_type| memory_type_heap_size(&props, memory_type))
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            sizes,
+            [
+                Some(8 << 30),
+                Some(16 << 30),
+                Some(16 << 30),
+                Some(256 << 20)
+            ]
+        );
+        assert_eq!(memory_type_heap(&props, 3).unwrap().size, 256 << 20);
+    }
+
+    #[test]
+    fn memory_type_heap_out_of_range_is_none() {
+        let mut props = discrete_gpu();
+        assert!(memory_type_heap(&props, 4).is_none());
+        assert_eq!(memory_type_heap_size(&props, 4), None);
+        assert_eq!(memory_type_heap_size(&props, u32::MAX), None);
+
+        // Heap index past the heaps of the device
+        props.memory_types.to_mut()[0].heap = 3;
+        assert!(memory_type_heap(&props, 0).is_none());
+        assert_eq!(memory_type_heap_size(&props, 0), None);
+    }
+}
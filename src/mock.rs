@@ -0,0 +1,327 @@
+use std::collections::HashMap;
+use std::ptr::NonNull;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Mutex, MutexGuard};
+
+use gpu_alloc_types::{
+    AllocationFlags, DeviceMapError, MappedMemoryRange, MemoryDevice, OutOfMemory,
+};
+
+use crate::lock_ignoring_poison;
+use crate::mapping::range_in_bounds;
+
+/// A memory device without a GPU, e.g. to test code using `gpu-alloc` in CI.
+///
+/// Memory objects are fake nonzero `u64` handles backed by host memory,
+/// which is allocated zeroed on the first map and kept until deallocation,
+/// so written data survives unmapping like it does with [`VulkanaliaMemoryDevice`].
+/// Mapping `vk::WHOLE_SIZE` maps up to the end of the memory object, an empty range
+/// or a range outside of it fails with [`DeviceMapError::MapFailed`].
+/// Flushes and invalidations always succeed.
+///
+/// Failures are injected with [`with_allocation_limit`](Self::with_allocation_limit)
+/// and [`with_map_failures`](Self::with_map_failures), reported with the same errors
+/// [`VulkanaliaMemoryDevice`] reports for the corresponding Vulkan results.
+/// Misuse which is undefined behavior for a real device, like freeing unknown memory
+/// or mapping memory twice, panics instead.
+///
+/// [`VulkanaliaMemoryDevice`]: crate::VulkanaliaMemoryDevice
+#[derive(Debug, Default)]
+pub struct MockMemoryDevice {
+    memory: Mutex<MockMemory>,
+    allocation_limit: Option<u64>,
+    map_failures: bool,
+    calls: MockCallCounters,
+}
+
+/// Number of calls of each [`MemoryDevice`] method made on a [`MockMemoryDevice`],
+/// including failed ones.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct MockCalls {
+    /// Number of `allocate_memory` calls.
+    pub allocate: u64,
+    /// Number of `deallocate_memory` calls.
+    pub deallocate: u64,
+    /// Number of `map_memory` calls.
+    pub map: u64,
+    /// Number of `unmap_memory` calls.
+    pub unmap: u64,
+    /// Number of `invalidate_memory_ranges` calls.
+    pub invalidate: u64,
+    /// Number of `flush_memory_ranges` calls.
+    pub flush: u64,
+}
+
+#[derive(Debug, Default)]
+struct MockCallCounters {
+    allocate: AtomicU64,
+    deallocate: AtomicU64,
+    map: AtomicU64,
+    unmap: AtomicU64,
+    invalidate: AtomicU64,
+    flush: AtomicU64,
+}
+
+#[derive(Debug, Default)]
+struct MockMemory {
+    objects: HashMap<u64, MockMemoryObject>,
+    next_handle: u64,
+    allocations: u64,
+}
+
+#[derive(Debug)]
+struct MockMemoryObject {
+    size: u64,
+    contents: Option<Box<[u8]>>,
+    mapped: bool,
+}
+
+impl MockMemoryDevice {
+    /// Creates a mock device which never fails.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Makes every allocation after the first `count` successful ones
+    /// fail with [`OutOfMemory::OutOfDeviceMemory`].
+    ///
+    /// Deallocations do not reset the count.
+    pub fn with_allocation_limit(self, count: u64) -> Self {
+        Self {
+            allocation_limit: Some(count),
+            ..self
+        }
+    }
+
+    /// Makes every map fail with [`DeviceMapError::MapFailed`] if `fail` is `true`.
+    pub fn with_map_failures(self, fail: bool) -> Self {
+        Self {
+            map_failures: fail,
+            ..self
+        }
+    }
+
+    /// Returns the number of calls made so far.
+    pub fn calls(&self) -> MockCalls {
+        let calls = &self.calls;
+        MockCalls {
+            allocate: calls.allocate.load(Ordering::Relaxed),
+            deallocate: calls.deallocate.load(Ordering::Relaxed),
+            map: calls.map.load(Ordering::Relaxed),
+            unmap: calls.unmap.load(Ordering::Relaxed),
+            invalidate: calls.invalidate.load(Ordering::Relaxed),
+            flush: calls.flush.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Returns the number of memory objects allocated and not yet deallocated.
+    pub fn live_count(&self) -> usize {
+        self.lock().objects.len()
+    }
+
+    /// Returns the size of the memory object, or `None` if it is not allocated.
+    pub fn allocation_size(&self, memory: u64) -> Option<u64> {
+        self.lock().objects.get(&memory).map(|object| object.size)
+    }
+
+    fn lock(&self) -> MutexGuard<'_, MockMemory> {
+        lock_ignoring_poison(&self.memory)
+    }
+}
+
+impl MemoryDevice<u64> for MockMemoryDevice {
+    unsafe fn allocate_memory(
+        &self,
+        size: u64,
+        _memory_type: u32,
+        _flags: AllocationFlags,
+    ) -> Result<u64, OutOfMemory> {
+        self.calls.allocate.fetch_add(1, Ordering::Relaxed);
+
+        let mut memory = self.lock();
+        if matches!(self.allocation_limit, Some(limit) if memory.allocations >= limit) {
+            return Err(OutOfMemory::OutOfDeviceMemory);
+        }
+        memory.allocations += 1;
+
+        // Zero is reserved for null handles
+        memory.next_handle += 1;
+        let handle = memory.next_handle;
+        memory.objects.insert(
+            handle,
+            MockMemoryObject {
+                size,
+                contents: None,
+                mapped: false,
+            },
+        );
+        Ok(handle)
+    }
+
+    unsafe fn deallocate_memory(&self, memory: u64) {
+        self.calls.deallocate.fetch_add(1, Ordering::Relaxed);
+
+        let removed = self.lock().objects.remove(&memory);
+        assert!(
+            removed.is_some(),
+            "Unknown memory object {memory} deallocated"
+        );
+    }
+
+    unsafe fn map_memory(
+        &self,
+        memory: &mut u64,
+        offset: u64,
+        size: u64,
+    ) -> Result<NonNull<u8>, DeviceMapError> {
+        self.calls.map.fetch_add(1, Ordering::Relaxed);
+
+        if self.map_failures {
+            return Err(DeviceMapError::MapFailed);
+        }
+
+        let mut objects = self.lock();
+        let Some(object) = objects.objects.get_mut(memory) else {
+            panic!("Unknown memory object {memory} mapped");
+        };
+        assert!(!object.mapped, "Memory object {memory} is already mapped");
+        if !range_in_bounds(offset, size, object.size) {
+            return Err(DeviceMapError::MapFailed);
+        }
+
+        let contents = match &mut object.contents {
+            Some(contents) => contents,
+            contents @ None => {
+                let len =
+                    usize::try_from(object.size).map_err(|_| DeviceMapError::OutOfHostMemory)?;
+                let mut buffer = Vec::new();
+                buffer
+                    .try_reserve_exact(len)
+                    .map_err(|_| DeviceMapError::OutOfHostMemory)?;
+                buffer.resize(len, 0);
+                contents.insert(buffer.into_boxed_slice())
+            }
+        };
+
+        // The range is checked to lie within the contents
+        let ptr = contents.as_mut_ptr().add(offset as usize);
+        object.mapped = true;
+        Ok(NonNull::new_unchecked(ptr))
+    }
+
+    unsafe fn unmap_memory(&self, memory: &mut u64) {
+        self.calls.unmap.fetch_add(1, Ordering::Relaxed);
+
+        let mut objects = self.lock();
+        let Some(object) = objects.objects.get_mut(memory) else {
+            panic!("Unknown memory object {memory} unmapped");
+        };
+        object.mapped = false;
+    }
+
+    unsafe fn invalidate_memory_ranges(
+        &self,
+        _ranges: &[MappedMemoryRange<'_, u64>],
+    ) -> Result<(), OutOfMemory> {
+        self.calls.invalidate.fetch_add(1, Ordering::Relaxed);
+        Ok(())
+    }
+
+    unsafe fn flush_memory_ranges(
+        &self,
+        _ranges: &[MappedMemoryRange<'_, u64>],
+    ) -> Result<(), OutOfMemory> {
+        self.calls.flush.fetch_add(1, Ordering::Relaxed);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use vulkanalia::vk;
+
+    use super::*;
+
+    #[test]
+    fn fails_allocations_over_limit() {
+        let device = MockMemoryDevice::new().with_allocation_limit(1);
+
+        unsafe {
+            let memory = device
+                .allocate_memory(16, 0, AllocationFlags::empty())
+                .unwrap();
+            device.deallocate_memory(memory);
+
+            // Deallocations do not reset the count
+            assert!(matches!(
+                device.allocate_memory(16, 0, AllocationFlags::empty()),
+                Err(OutOfMemory::OutOfDeviceMemory)
+            ));
+        }
+
+        assert_eq!(device.live_count(), 0);
+        assert_eq!(device.calls().allocate, 2);
+        assert_eq!(device.calls().deallocate, 1);
+    }
+
+    #[test]
+    fn fails_maps_when_requested() {
+        let device = MockMemoryDevice::new().with_map_failures(true);
+
+        unsafe {
+            let mut memory = device
+                .allocate_memory(16, 0, AllocationFlags::empty())
+                .unwrap();
+            assert!(matches!(
+                device.map_memory(&mut memory, 0, 16),
+                Err(DeviceMapError::MapFailed)
+            ));
+            device.deallocate_memory(memory);
+        }
+
+        assert_eq!(device.calls().map, 1);
+    }
+
+    #[test]
+    fn maps_whole_size_to_the_end() {
+        let device = MockMemoryDevice::new();
+
+        unsafe {
+            let mut memory = device
+                .allocate_memory(16, 0, AllocationFlags::empty())
+                .unwrap();
+
+            let ptr = device.map_memory(&mut memory, 4, vk::WHOLE_SIZE).unwrap();
+            ptr.as_ptr().write(7);
+            device.unmap_memory(&mut memory);
+
+            let ptr = device.map_memory(&mut memory, 0, 16).unwrap();
+            assert_eq!(ptr.as_ptr().add(4).read(), 7);
+            device.unmap_memory(&mut memory);
+
+            device.deallocate_memory(memory);
+        }
+    }
+
+    #[test]
+    fn fails_maps_out_of_bounds() {
+        let device = MockMemoryDevice::new();
+
+        unsafe {
+            let mut memory = device
+                .allocate_memory(16, 0, AllocationFlags::empty())
+                .unwrap();
+            for (offset, size) in [(16, vk::WHOLE_SIZE), (8, 9), (1, u64::MAX - 1), (0, 0)] {
+                assert!(matches!(
+                    device.map_memory(&mut memory, offset, size),
+                    Err(DeviceMapError::MapFailed)
+                ));
+            }
+
+            // Failed maps leave the memory object unmapped
+            device.map_memory(&mut memory, 0, 16).unwrap();
+            device.unmap_memory(&mut memory);
+            device.deallocate_memory(memory);
+        }
+    }
+}
@@ -0,0 +1,126 @@
+use std::mem::ManuallyDrop;
+
+use gpu_alloc_types::{AllocationFlags, MemoryDevice};
+use vulkanalia::prelude::v1_0::*;
+
+use crate::{VulkanaliaMemoryDevice, VulkanaliaMemoryError};
+
+/// Memory object freed on drop.
+///
+/// Created with [`VulkanaliaMemoryDevice::allocate_memory_owned`]
+/// or [`from_raw`](Self::from_raw) with any memory device,
+/// e.g. `MockMemoryDevice` with its `u64` memory handles as `M`.
+/// The device is borrowed for the lifetime of the memory object,
+/// so the memory cannot outlive it.
+pub struct OwnedDeviceMemory<'a, D = VulkanaliaMemoryDevice, M = vk::DeviceMemory>
+where
+    D: MemoryDevice<M> + ?Sized,
+    M: Copy,
+{
+    device: &'a D,
+    memory: M,
+}
+
+impl<'a, D, M> OwnedDeviceMemory<'a, D, M>
+where
+    D: MemoryDevice<M> + ?Sized,
+    M: Copy,
+{
+    /// Takes ownership of a memory object, freeing it when the guard is dropped.
+    ///
+    /// # Safety
+    ///
+    /// `memory` must have been allocated from the `device` and must not be freed
+    /// through other means.
+    pub unsafe fn from_raw(device: &'a D, memory: M) -> Self {
+        OwnedDeviceMemory { device, memory }
+    }
+
+    /// Returns the memory object, e.g. to bind resources to it.
+    pub fn raw(&self) -> M {
+        self.memory
+    }
+
+    /// Returns the memory object without freeing it.
+    /// The caller becomes responsible for freeing it.
+    pub fn leak(self) -> M {
+        ManuallyDrop::new(self).memory
+    }
+}
+
+impl<D, M> Drop for OwnedDeviceMemory<'_, D, M>
+where
+    D: MemoryDevice<M> + ?Sized,
+    M: Copy,
+{
+    fn drop(&mut self) {
+        unsafe {
+            // SAFETY: The memory object was allocated from this device
+            // and is owned by the guard.
+            self.device.deallocate_memory(self.memory);
+        }
+    }
+}
+
+impl VulkanaliaMemoryDevice {
+    /// Allocates new memory object which is freed when the returned guard is dropped.
+    ///
+    /// # Safety
+    ///
+    /// Same as for [`MemoryDevice::allocate_memory`].
+    /// Resources bound to the memory must be destroyed before the guard is dropped.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(target = "gpu_alloc::vulkanalia", skip(self))
+    )]
+    pub unsafe fn allocate_memory_owned(
+        &self,
+        size: u64,
+        memory_type: u32,
+        flags: AllocationFlags,
+    ) -> Result<OwnedDeviceMemory<'_>, VulkanaliaMemoryError> {
+        let memory = self.allocate_memory_with(size, memory_type, flags, &Default::default())?;
+        Ok(OwnedDeviceMemory::from_raw(self, memory))
+    }
+}
+
+#[cfg(all(test, feature = "mock"))]
+mod tests {
+    use super::*;
+    use crate::MockMemoryDevice;
+
+    unsafe fn allocate_owned(
+        device: &MockMemoryDevice,
+    ) -> OwnedDeviceMemory<'_, MockMemoryDevice, u64> {
+        let memory = device
+            .allocate_memory(256, 0, AllocationFlags::empty())
+            .unwrap();
+        OwnedDeviceMemory::from_raw(device, memory)
+    }
+
+    #[test]
+    fn drop_frees_memory() {
+        let device = MockMemoryDevice::new();
+
+        unsafe {
+            let owned = allocate_owned(&device);
+            assert_eq!(device.live_count(), 1);
+            drop(owned);
+        }
+
+        assert_eq!(device.calls().deallocate, 1);
+        assert_eq!(device.live_count(), 0);
+    }
+
+    #[test]
+    fn leak_keeps_memory() {
+        let device = MockMemoryDevice::new();
+
+        let memory = unsafe { allocate_owned(&device) }.leak();
+
+        assert_eq!(device.calls().deallocate, 0);
+        assert_eq!(device.live_count(), 1);
+        assert_eq!(device.allocation_size(memory), Some(256));
+        unsafe { device.deallocate_memory(memory) };
+    }
+}
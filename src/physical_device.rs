@@ -0,0 +1,582 @@
+use std::collections::BTreeSet;
+
+#[cfg(feature = "smallvec")]
+use smallvec::SmallVec;
+use vulkanalia::prelude::v1_0::*;
+use vulkanalia::vk::{InstanceV1_1, KhrExternalMemoryCapabilitiesExtensionInstanceCommands};
+
+/// Memory budget and usage of each memory heap,
+/// as reported by `VkPhysicalDeviceMemoryBudgetPropertiesEXT`.
+///
+/// Arrays are indexed the same way as `DeviceProperties::memory_heaps`,
+/// entries past the number of memory heaps are zero.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MemoryBudget {
+    /// Estimated amount of memory the process can allocate from each heap
+    /// before allocations may fail or cause performance degradation.
+    pub budget: [u64; vk::MAX_MEMORY_HEAPS],
+    /// Estimated amount of memory currently used by the process in each heap.
+    pub usage: [u64; vk::MAX_MEMORY_HEAPS],
+}
+
+impl MemoryBudget {
+    /// Returns the budget left in the heap, saturating at zero when the usage exceeds it.
+    ///
+    /// Returns `None` when `heap` is not less than `vk::MAX_MEMORY_HEAPS`.
+    pub fn remaining(&self, heap: usize) -> Option<u64> {
+        let budget = self.budget.get(heap)?;
+        let usage = self.usage.get(heap)?;
+        Some(budget.saturating_sub(*usage))
+    }
+}
+
+/// Queries current memory budget of the physical device.
+///
+/// Returns `None` when the device does not support extension "VK_EXT_memory_budget"
+/// or `vkGetPhysicalDeviceMemoryProperties2` is not available for the `version`.
+/// The values are a snapshot and should be re-queried periodically,
+/// e.g. once per frame.
+///
+/// # Safety
+///
+/// - `version` must not be higher than the `api_version` of the `instance`.
+/// - `physical_device` must be queried from an [`Instance`] associated with this `instance`.
+pub unsafe fn memory_budget(
+    instance: &Instance,
+    version: u32,
+    physical_device: vk::PhysicalDevice,
+) -> VkResult<Option<MemoryBudget>> {
+    if !properties2_supported(instance, version)
+        || !device_extension_supported(
+            instance,
+            physical_device,
+            &vk::EXT_MEMORY_BUDGET_EXTENSION.name,
+        )?
+    {
+        return Ok(None);
+    }
+
+    let mut properties = vk::PhysicalDeviceMemoryProperties2::builder();
+    let mut budget = vk::PhysicalDeviceMemoryBudgetPropertiesEXT::builder();
+    properties = properties.push_next(&mut budget);
+    instance.get_physical_device_memory_properties2(physical_device, &mut properties);
+
+    Ok(Some(MemoryBudget {
+        budget: budget.heap_budget,
+        usage: budget.heap_usage,
+    }))
+}
+
+/// Queries `VkPhysicalDeviceExternalMemoryHostPropertiesEXT::minImportedHostPointerAlignment`,
+/// the alignment of host pointers and sizes imported with
+/// [`VulkanaliaMemoryDevice::import_host_pointer_memory`](crate::VulkanaliaMemoryDevice::import_host_pointer_memory).
+///
+/// Returns `None` when the device does not support extension "VK_EXT_external_memory_host"
+/// or `vkGetPhysicalDeviceProperties2` is not available for the `version`.
+///
+/// # Safety
+///
+/// - `version` must not be higher than the `api_version` of the `instance`.
+/// - `physical_device` must be queried from an [`Instance`] associated with this `instance`.
+pub unsafe fn external_memory_host_properties(
+    instance: &Instance,
+    version: u32,
+    physical_device: vk::PhysicalDevice,
+) -> VkResult<Option<u64>> {
+    if !properties2_supported(instance, version)
+        || !device_extension_supported(
+            instance,
+            physical_device,
+            &vk::EXT_EXTERNAL_MEMORY_HOST_EXTENSION.name,
+        )?
+    {
+        return Ok(None);
+    }
+
+    let mut properties = vk::PhysicalDeviceProperties2::builder();
+    let mut host_properties = vk::PhysicalDeviceExternalMemoryHostPropertiesEXT::builder();
+    properties = properties.push_next(&mut host_properties);
+    instance.get_physical_device_properties2(physical_device, &mut properties);
+
+    Ok(Some(host_properties.min_imported_host_pointer_alignment))
+}
+
+/// Queries `VkPhysicalDeviceMaintenance4Properties::maxBufferSize`,
+/// the maximum size of a buffer which can be created, independent of allocation size limits.
+///
+/// Returns `None` when the `version` is below Vulkan 1.3 and the device does not support
+/// extension "VK_KHR_maintenance4", or `vkGetPhysicalDeviceProperties2` is not available.
+///
+/// # Safety
+///
+/// - `version` must not be higher than the `api_version` of the `instance`
+///   and the physical device api version.
+/// - `physical_device` must be queried from an [`Instance`] associated with this `instance`.
+pub unsafe fn max_buffer_size(
+    instance: &Instance,
+    version: u32,
+    physical_device: vk::PhysicalDevice,
+) -> VkResult<Option<u64>> {
+    if !properties2_supported(instance, version) {
+        return Ok(None);
+    }
+    if !maintenance4_core(version)
+        && !device_extension_supported(
+            instance,
+            physical_device,
+            &vk::KHR_MAINTENANCE4_EXTENSION.name,
+        )?
+    {
+        return Ok(None);
+    }
+
+    let mut properties = vk::PhysicalDeviceProperties2::builder();
+    let mut maintenance4 = vk::PhysicalDeviceMaintenance4Properties::builder();
+    properties = properties.push_next(&mut maintenance4);
+    instance.get_physical_device_properties2(physical_device, &mut properties);
+
+    Ok(Some(maintenance4.max_buffer_size))
+}
+
+/// Queries whether buffers with `usage` can be exported or imported as `handle_type`,
+/// using `vkGetPhysicalDeviceExternalBufferProperties` for Vulkan 1.1 and above
+/// and `vkGetPhysicalDeviceExternalBufferPropertiesKHR` otherwise.
+///
+/// Check `external_memory_features` of the result for `EXPORTABLE` and `IMPORTABLE`
+/// and whether a dedicated allocation is required.
+///
+/// # Safety
+///
+/// - `version` must not be higher than the `api_version` of the `instance`.
+/// - For Vulkan 1.0 instance extension "VK_KHR_external_memory_capabilities" must be enabled.
+/// - `physical_device` must be queried from an [`Instance`] associated with this `instance`.
+/// - `handle_type` must be a single handle type.
+pub unsafe fn external_buffer_properties(
+    instance: &Instance,
+    version: u32,
+    physical_device: vk::PhysicalDevice,
+    usage: vk::BufferUsageFlags,
+    handle_type: vk::ExternalMemoryHandleTypeFlags,
+) -> vk::ExternalMemoryProperties {
+    let info = external_buffer_info(usage, handle_type);
+    let mut properties = vk::ExternalBufferProperties::builder();
+
+    if external_memory_capabilities_core(version) {
+        instance.get_physical_device_external_buffer_properties(
+            physical_device,
+            &info,
+            &mut properties,
+        );
+    } else {
+        instance.get_physical_device_external_buffer_properties_khr(
+            physical_device,
+            &info,
+            &mut properties,
+        );
+    }
+
+    properties.external_memory_properties
+}
+
+fn external_buffer_info(
+    usage: vk::BufferUsageFlags,
+    handle_type: vk::ExternalMemoryHandleTypeFlags,
+) -> vk::PhysicalDeviceExternalBufferInfo {
+    vk::PhysicalDeviceExternalBufferInfo::builder()
+        .usage(usage)
+        .handle_type(handle_type)
+        .build()
+}
+
+/// Returns `true` if "VK_KHR_external_memory_capabilities" is core in the `version`.
+fn external_memory_capabilities_core(version: u32) -> bool {
+    vk::version_minor(version) >= 1
+}
+
+/// Sparse resource properties of the physical device.
+///
+/// Contains all fields of `VkPhysicalDeviceSparseProperties`
+/// along with the sparse-related limits of `VkPhysicalDeviceLimits`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct SparseProperties {
+    /// `VkPhysicalDeviceLimits::sparseAddressSpaceSize`,
+    /// total address space available for sparse resources in bytes.
+    pub sparse_address_space_size: u64,
+    /// `VkPhysicalDeviceLimits::maxMemoryAllocationCount`,
+    /// also limits memory objects backing sparse resources.
+    pub max_memory_allocation_count: u32,
+    /// Single-sample 2D images use the standard sparse block shapes.
+    pub residency_standard_2d_block_shape: bool,
+    /// Multisample 2D images use the standard sparse block shapes.
+    pub residency_standard_2d_multisample_block_shape: bool,
+    /// 3D images use the standard sparse block shapes.
+    pub residency_standard_3d_block_shape: bool,
+    /// Mip levels with dimensions not multiple of the block size
+    /// may be placed in the mip tail.
+    pub residency_aligned_mip_size: bool,
+    /// Non-resident regions are guaranteed to read as zero.
+    pub residency_non_resident_strict: bool,
+}
+
+/// Queries sparse resource properties of the physical device.
+///
+/// # Safety
+///
+/// `physical_device` must be queried from an [`Instance`] associated with this `instance`.
+pub unsafe fn sparse_properties(
+    instance: &Instance,
+    physical_device: vk::PhysicalDevice,
+) -> SparseProperties {
+    SparseProperties::from(&instance.get_physical_device_properties(physical_device))
+}
+
+/// Collects sparse properties from already queried physical device properties.
+impl From<&vk::PhysicalDeviceProperties> for SparseProperties {
+    fn from(properties: &vk::PhysicalDeviceProperties) -> Self {
+        let limits = &properties.limits;
+        let sparse = &properties.sparse_properties;
+
+        Self {
+            sparse_address_space_size: limits.sparse_address_space_size,
+            max_memory_allocation_count: limits.max_memory_allocation_count,
+            residency_standard_2d_block_shape: sparse.residency_standard_2d_block_shape != 0,
+            residency_standard_2d_multisample_block_shape: sparse
+                .residency_standard_2d_multisample_block_shape
+                != 0,
+            residency_standard_3d_block_shape: sparse.residency_standard_3d_block_shape != 0,
+            residency_aligned_mip_size: sparse.residency_aligned_mip_size != 0,
+            residency_non_resident_strict: sparse.residency_non_resident_strict != 0,
+        }
+    }
+}
+
+/// Limits of `VkPhysicalDeviceLimits` relevant for manual sub-allocation,
+/// which are not part of `DeviceProperties`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct RelevantLimits {
+    /// Granularity in bytes at which linear and optimal resources
+    /// can be placed next to each other in the same memory object.
+    pub buffer_image_granularity: u64,
+    /// Optimal buffer offset alignment for buffer-image copies.
+    pub optimal_buffer_copy_offset_alignment: u64,
+    /// Optimal buffer row pitch alignment for buffer-image copies.
+    pub optimal_buffer_copy_row_pitch_alignment: u64,
+    /// Minimum alignment of host pointers returned by `vkMapMemory`,
+    /// relative to the start of the memory object.
+    pub min_memory_map_alignment: usize,
+    /// Minimum alignment of uniform buffer offsets.
+    pub min_uniform_buffer_offset_alignment: u64,
+    /// Minimum alignment of storage buffer offsets.
+    pub min_storage_buffer_offset_alignment: u64,
+    /// Minimum alignment of texel buffer offsets.
+    pub min_texel_buffer_offset_alignment: u64,
+}
+
+/// Queries limits relevant for manual sub-allocation.
+///
+/// # Safety
+///
+/// `physical_device` must be queried from an [`Instance`] associated with this `instance`.
+pub unsafe fn relevant_limits(
+    instance: &Instance,
+    physical_device: vk::PhysicalDevice,
+) -> RelevantLimits {
+    RelevantLimits::from(
+        &instance
+            .get_physical_device_properties(physical_device)
+            .limits,
+    )
+}
+
+/// Collects relevant limits from already queried physical device limits.
+impl From<&vk::PhysicalDeviceLimits> for RelevantLimits {
+    fn from(limits: &vk::PhysicalDeviceLimits) -> Self {
+        Self {
+            buffer_image_granularity: limits.buffer_image_granularity,
+            optimal_buffer_copy_offset_alignment: limits.optimal_buffer_copy_offset_alignment,
+            optimal_buffer_copy_row_pitch_alignment: limits.optimal_buffer_copy_row_pitch_alignment,
+            min_memory_map_alignment: limits.min_memory_map_alignment,
+            min_uniform_buffer_offset_alignment: limits.min_uniform_buffer_offset_alignment,
+            min_storage_buffer_offset_alignment: limits.min_storage_buffer_offset_alignment,
+            min_texel_buffer_offset_alignment: limits.min_texel_buffer_offset_alignment,
+        }
+    }
+}
+
+/// Queries `VkPhysicalDeviceLimits::bufferImageGranularity`,
+/// see [`RelevantLimits::buffer_image_granularity`].
+///
+/// # Safety
+///
+/// `physical_device` must be queried from an [`Instance`] associated with this `instance`.
+pub unsafe fn buffer_image_granularity(
+    instance: &Instance,
+    physical_device: vk::PhysicalDevice,
+) -> u64 {
+    relevant_limits(instance, physical_device).buffer_image_granularity
+}
+
+/// Kind of resource as seen by the `bufferImageGranularity` aliasing rule.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ResourceType {
+    /// Buffer or image with `LINEAR` tiling.
+    Linear,
+    /// Image with `OPTIMAL` tiling.
+    Optimal,
+}
+
+/// Returns `true` if resources of types `a` and `b` placed at adjacent offsets
+/// of one memory object must not share a page of [`buffer_image_granularity`] bytes.
+///
+/// Per "Buffer-Image Granularity" section of the Vulkan spec, only linear and
+/// optimal resources need to be separated, resources of the same kind may share a page.
+pub fn needs_granularity_separation(a: ResourceType, b: ResourceType) -> bool {
+    a != b
+}
+
+/// Returns `true` if `vkGetPhysicalDeviceProperties2` and friends can be used.
+fn properties2_supported(instance: &Instance, version: u32) -> bool {
+    properties2_available(version, instance.extensions())
+}
+
+/// Returns `true` if `vkGetPhysicalDeviceProperties2` and friends are core in the `version`
+/// or provided by one of enabled `instance_extensions`.
+fn properties2_available(version: u32, instance_extensions: &BTreeSet<vk::ExtensionName>) -> bool {
+    vk::version_minor(version) > 0
+        || instance_extensions.contains(&vk::KHR_GET_PHYSICAL_DEVICE_PROPERTIES2_EXTENSION.name)
+}
+
+/// Returns `true` if "VK_KHR_maintenance4" is core in the `version`.
+fn maintenance4_core(version: u32) -> bool {
+    vk::version_minor(version) >= 3
+}
+
+unsafe fn device_extension_supported(
+    instance: &Instance,
+    physical_device: vk::PhysicalDevice,
+    name: &vk::ExtensionName,
+) -> VkResult<bool> {
+    let mut supported = [false];
+    check_extensions(instance, physical_device, &[name], &mut supported)?;
+    Ok(supported[0])
+}
+
+/// Returns whether each of the device extensions is supported by the physical device,
+/// in the same order as `names`.
+///
+/// Requires `smallvec` feature.
+///
+/// # Safety
+///
+/// `physical_device` must be queried from an [`Instance`] associated with this `instance`.
+#[cfg(feature = "smallvec")]
+pub unsafe fn supported_extensions(
+    instance: &Instance,
+    physical_device: vk::PhysicalDevice,
+    names: &[&vk::ExtensionName],
+) -> VkResult<SmallVec<[bool; 8]>> {
+    let mut supported = SmallVec::from_elem(false, names.len());
+    check_extensions(instance, physical_device, names, &mut supported)?;
+    Ok(supported)
+}
+
+/// Sets `supported[i]` to `true` if extension `names[i]` is supported by the physical device.
+/// Entries of `supported` which are already `true` are not looked up.
+pub(crate) unsafe fn check_extensions(
+    instance: &Instance,
+    physical_device: vk::PhysicalDevice,
+    names: &[&vk::ExtensionName],
+    supported: &mut [bool],
+) -> VkResult<()> {
+    debug_assert_eq!(names.len(), supported.len());
+
+    if supported.iter().all(|supported| *supported) {
+        return Ok(());
+    }
+
+    let extensions = instance.enumerate_device_extension_properties(physical_device, None)?;
+    mark_supported(&extensions, names, supported);
+    Ok(())
+}
+
+/// Sets `supported[i]` to `true` if extension `names[i]` is among `extensions`,
+/// see [`check_extensions`].
+fn mark_supported(
+    extensions: &[vk::ExtensionProperties],
+    names: &[&vk::ExtensionName],
+    supported: &mut [bool],
+) {
+    let mut to_find = supported.iter().filter(|supported| !**supported).count();
+    if to_find == 0 {
+        return;
+    }
+
+    'extensions: for extension in extensions {
+        for (name, supported) in names.iter().zip(supported.iter_mut()) {
+            if !*supported && *name == &extension.extension_name {
+                *supported = true;
+                to_find -= 1;
+                if to_find == 0 {
+                    break 'extensions;
+                }
+                continue 'extensions;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn granularity_separates_only_different_resource_types() {
+        use ResourceType::{Linear, Optimal};
+
+        assert!(!needs_granularity_separation(Linear, Linear));
+        assert!(needs_granularity_separation(Linear, Optimal));
+        assert!(needs_granularity_separation(Optimal, Linear));
+        assert!(!needs_granularity_separation(Optimal, Optimal));
+    }
+
+    #[test]
+    fn mark_supported_finds_present_extensions() {
+        let extensions = [
+            vk::ExtensionProperties {
+                extension_name: vk::EXT_MEMORY_BUDGET_EXTENSION.name,
+                spec_version: 1,
+            },
+            vk::ExtensionProperties {
+                extension_name: vk::KHR_MAINTENANCE4_EXTENSION.name,
+                spec_version: 2,
+            },
+        ];
+        let names = [
+            &vk::KHR_MAINTENANCE4_EXTENSION.name,
+            &vk::EXT_EXTERNAL_MEMORY_HOST_EXTENSION.name,
+            &vk::EXT_MEMORY_BUDGET_EXTENSION.name,
+        ];
+
+        let mut supported = [false; 3];
+        mark_supported(&extensions, &names, &mut supported);
+        assert_eq!(supported, [true, false, true]);
+
+        // Entries already marked are kept
+        let mut supported = [false, true, false];
+        mark_supported(&[], &names, &mut supported);
+        assert_eq!(supported, [false, true, false]);
+    }
+
+    #[test]
+    fn sparse_properties_from_device_properties() {
+        let mut properties = vk::PhysicalDeviceProperties::default();
+        properties.limits.sparse_address_space_size = 1 << 40;
+        properties.limits.max_memory_allocation_count = 4096;
+        properties
+            .sparse_properties
+            .residency_standard_2d_block_shape = vk::TRUE;
+        properties
+            .sparse_properties
+            .residency_standard_3d_block_shape = vk::TRUE;
+        properties.sparse_properties.residency_non_resident_strict = vk::TRUE;
+
+        assert_eq!(
+            SparseProperties::from(&properties),
+            SparseProperties {
+                sparse_address_space_size: 1 << 40,
+                max_memory_allocation_count: 4096,
+                residency_standard_2d_block_shape: true,
+                residency_standard_2d_multisample_block_shape: false,
+                residency_standard_3d_block_shape: true,
+                residency_aligned_mip_size: false,
+                residency_non_resident_strict: true,
+            }
+        );
+        assert_eq!(
+            SparseProperties::from(&vk::PhysicalDeviceProperties::default()),
+            SparseProperties::default()
+        );
+    }
+
+    #[test]
+    fn relevant_limits_from_device_limits() {
+        let limits = vk::PhysicalDeviceLimits {
+            buffer_image_granularity: 1024,
+            optimal_buffer_copy_offset_alignment: 4,
+            optimal_buffer_copy_row_pitch_alignment: 8,
+            min_memory_map_alignment: 64,
+            min_uniform_buffer_offset_alignment: 256,
+            min_storage_buffer_offset_alignment: 32,
+            min_texel_buffer_offset_alignment: 16,
+            // Not relevant
+            non_coherent_atom_size: 128,
+            ..Default::default()
+        };
+
+        assert_eq!(
+            RelevantLimits::from(&limits),
+            RelevantLimits {
+                buffer_image_granularity: 1024,
+                optimal_buffer_copy_offset_alignment: 4,
+                optimal_buffer_copy_row_pitch_alignment: 8,
+                min_memory_map_alignment: 64,
+                min_uniform_buffer_offset_alignment: 256,
+                min_storage_buffer_offset_alignment: 32,
+                min_texel_buffer_offset_alignment: 16,
+            }
+        );
+    }
+
+    #[test]
+    fn properties2_needs_vulkan_1_1_or_extension() {
+        let none = BTreeSet::new();
+        let properties2 = BTreeSet::from([vk::KHR_GET_PHYSICAL_DEVICE_PROPERTIES2_EXTENSION.name]);
+        let other = BTreeSet::from([vk::KHR_SURFACE_EXTENSION.name]);
+
+        assert!(!properties2_available(vk::make_version(1, 0, 0), &none));
+        assert!(!properties2_available(vk::make_version(1, 0, 0), &other));
+        assert!(properties2_available(
+            vk::make_version(1, 0, 0),
+            &properties2
+        ));
+        assert!(properties2_available(vk::make_version(1, 1, 0), &none));
+        assert!(properties2_available(vk::make_version(1, 3, 0), &none));
+    }
+
+    #[test]
+    fn maintenance4_is_core_since_vulkan_1_3() {
+        assert!(!maintenance4_core(vk::make_version(1, 0, 0)));
+        assert!(!maintenance4_core(vk::make_version(1, 2, 200)));
+        assert!(maintenance4_core(vk::make_version(1, 3, 0)));
+        assert!(maintenance4_core(vk::make_version(1, 4, 0)));
+    }
+
+    #[test]
+    fn external_buffer_info_describes_the_buffer() {
+        let info = external_buffer_info(
+            vk::BufferUsageFlags::STORAGE_BUFFER,
+            vk::ExternalMemoryHandleTypeFlags::OPAQUE_FD,
+        );
+
+        assert_eq!(
+            info.s_type,
+            vk::StructureType::PHYSICAL_DEVICE_EXTERNAL_BUFFER_INFO
+        );
+        assert!(info.next.is_null());
+        assert_eq!(info.flags, vk::BufferCreateFlags::empty());
+        assert_eq!(info.usage, vk::BufferUsageFlags::STORAGE_BUFFER);
+        assert_eq!(
+            info.handle_type,
+            vk::ExternalMemoryHandleTypeFlags::OPAQUE_FD
+        );
+    }
+
+    #[test]
+    fn external_memory_capabilities_are_core_since_vulkan_1_1() {
+        assert!(!external_memory_capabilities_core(vk::make_version(
+            1, 0, 0
+        )));
+        assert!(external_memory_capabilities_core(vk::make_version(1, 1, 0)));
+        assert!(external_memory_capabilities_core(vk::make_version(1, 3, 0)));
+    }
+}
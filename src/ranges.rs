@@ -0,0 +1,618 @@
+use gpu_alloc_types::{DeviceProperties, MappedMemoryRange, OutOfMemory};
+#[cfg(feature = "smallvec")]
+use smallvec::SmallVec;
+use vulkanalia::prelude::v1_0::*;
+
+use crate::{is_host_coherent, unexpected_error, VulkanaliaMemoryDevice, VulkanaliaMemoryError};
+
+/// Number of ranges [`MemoryDevice::flush_memory_ranges`](gpu_alloc_types::MemoryDevice::flush_memory_ranges)
+/// and [`MemoryDevice::invalidate_memory_ranges`](gpu_alloc_types::MemoryDevice::invalidate_memory_ranges)
+/// convert to Vulkan structures on the stack.
+///
+/// More ranges are collected into a heap allocated buffer.
+/// Use [`VulkanaliaMemoryDevice::flush_memory_ranges_n`] and
+/// [`VulkanaliaMemoryDevice::invalidate_memory_ranges_n`] to pick another capacity.
+pub const DEFAULT_RANGES_INLINE_CAPACITY: usize = 4;
+
+/// Mapped region of a memory object along with its memory type,
+/// flushed with [`VulkanaliaMemoryDevice::flush_ranges_aligned`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct TypedMemoryRange {
+    /// Mapped memory object.
+    pub memory: vk::DeviceMemory,
+    /// Index of the memory type `memory` was allocated with.
+    pub memory_type: u32,
+    /// Offset of the range from the start of the memory object.
+    pub offset: u64,
+    /// Size of the range in bytes or `vk::WHOLE_SIZE`.
+    pub size: u64,
+    /// Size of the memory object, used to clamp the aligned range.
+    pub allocation_size: u64,
+}
+
+/// Converts `gpu-alloc-types` range into Vulkan structure,
+/// e.g. to flush it along with other ranges with `vkFlushMappedMemoryRanges`.
+///
+/// Offset and size are copied as is, without any alignment, so `vk::WHOLE_SIZE` is kept.
+pub fn to_vk_mapped_range(
+    range: &MappedMemoryRange<'_, vk::DeviceMemory>,
+) -> vk::MappedMemoryRange {
+    vk::MappedMemoryRange::builder()
+        .memory(*range.memory)
+        .offset(range.offset)
+        .size(range.size)
+        .build()
+}
+
+/// Expands a mapped memory range to satisfy `non_coherent_atom_size` alignment,
+/// returning the new `(offset, size)` pair.
+///
+/// The offset is rounded down and the end of the range is rounded up to a multiple
+/// of `atom_size`. If the rounded end would exceed `allocation_size`,
+/// the size is clamped to `vk::WHOLE_SIZE`, which is always valid for an aligned offset.
+/// A zero `size` yields a zero size and `vk::WHOLE_SIZE` is kept as is,
+/// only the offset is rounded down.
+/// An `offset` at or past `allocation_size` yields an empty range, which must not be flushed.
+pub fn align_range_to_atom(
+    offset: u64,
+    size: u64,
+    atom_size: u64,
+    allocation_size: u64,
+) -> (u64, u64) {
+    let atom_size = atom_size.max(1);
+    let aligned_offset = offset - offset % atom_size;
+
+    if size == 0 || offset >= allocation_size {
+        return (aligned_offset, 0);
+    }
+    if size == vk::WHOLE_SIZE {
+        // Extends to the end of the allocation, which needs no alignment
+        return (aligned_offset, vk::WHOLE_SIZE);
+    }
+
+    let aligned_end = offset
+        .checked_add(size)
+        .and_then(|end| end.checked_add(atom_size - 1))
+        .map(|end| end - end % atom_size);
+
+    match aligned_end {
+        Some(aligned_end) if aligned_end <= allocation_size => {
+            (aligned_offset, aligned_end - aligned_offset)
+        }
+        _ => (aligned_offset, vk::WHOLE_SIZE),
+    }
+}
+
+/// Rounds `size` up to a multiple of `atom_size`, so that flushing or invalidating
+/// the whole allocation with an explicit size is always valid.
+///
+/// Zero `atom_size` is treated as 1. Sizes which cannot be padded
+/// without overflow are returned as is.
+pub fn pad_to_non_coherent_atom(size: u64, atom_size: u64) -> u64 {
+    let atom_size = atom_size.max(1);
+    match size.checked_add(atom_size - 1) {
+        Some(end) => end - end % atom_size,
+        None => size,
+    }
+}
+
+/// Returns `non_coherent_atom_size` limit of the properties, at least 1.
+pub fn non_coherent_atom_size(props: &DeviceProperties<'_>) -> u64 {
+    props.non_coherent_atom_size.max(1)
+}
+
+impl VulkanaliaMemoryDevice {
+    /// Flushes ranges of memory mapped regions like
+    /// [`MemoryDevice::flush_memory_ranges`](gpu_alloc_types::MemoryDevice::flush_memory_ranges) does,
+    /// converting up to `N` ranges on the stack.
+    ///
+    /// Each inline range takes 40 bytes of stack, so larger `N` only pays off
+    /// when that many ranges are flushed at once regularly.
+    ///
+    /// # Safety
+    ///
+    /// Same as for [`MemoryDevice::flush_memory_ranges`](gpu_alloc_types::MemoryDevice::flush_memory_ranges).
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            target = "gpu_alloc::vulkanalia",
+            skip(self, ranges),
+            fields(ranges = ranges.len())
+        )
+    )]
+    pub unsafe fn flush_memory_ranges_n<const N: usize>(
+        &self,
+        ranges: &[MappedMemoryRange<'_, vk::DeviceMemory>],
+    ) -> Result<(), OutOfMemory> {
+        self.flush_ranges::<N>(ranges)
+    }
+
+    /// Invalidates ranges of memory mapped regions like
+    /// [`MemoryDevice::invalidate_memory_ranges`](gpu_alloc_types::MemoryDevice::invalidate_memory_ranges) does,
+    /// converting up to `N` ranges on the stack.
+    ///
+    /// Each inline range takes 40 bytes of stack, so larger `N` only pays off
+    /// when that many ranges are invalidated at once regularly.
+    ///
+    /// # Safety
+    ///
+    /// Same as for [`MemoryDevice::invalidate_memory_ranges`](gpu_alloc_types::MemoryDevice::invalidate_memory_ranges).
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            target = "gpu_alloc::vulkanalia",
+            skip(self, ranges),
+            fields(ranges = ranges.len())
+        )
+    )]
+    pub unsafe fn invalidate_memory_ranges_n<const N: usize>(
+        &self,
+        ranges: &[MappedMemoryRange<'_, vk::DeviceMemory>],
+    ) -> Result<(), OutOfMemory> {
+        self.invalidate_ranges::<N>(ranges)
+    }
+
+    pub(crate) unsafe fn flush_ranges<const N: usize>(
+        &self,
+        ranges: &[MappedMemoryRange<'_, vk::DeviceMemory>],
+    ) -> Result<(), OutOfMemory> {
+        self.device
+            .flush_mapped_memory_ranges(
+                &ranges
+                    .iter()
+                    .map(to_vk_mapped_range)
+                    .collect::<RangeVec<N>>(),
+            )
+            .map_err(range_error)
+    }
+
+    pub(crate) unsafe fn invalidate_ranges<const N: usize>(
+        &self,
+        ranges: &[MappedMemoryRange<'_, vk::DeviceMemory>],
+    ) -> Result<(), OutOfMemory> {
+        self.device
+            .invalidate_mapped_memory_ranges(
+                &ranges
+                    .iter()
+                    .map(to_vk_mapped_range)
+                    .collect::<RangeVec<N>>(),
+            )
+            .map_err(range_error)
+    }
+
+    /// Makes host writes to a mapped region of `memory` visible to the device.
+    ///
+    /// Flushes a single range aligned with [`align_range_to_atom`].
+    /// For host coherent memory this is not required, but still valid.
+    ///
+    /// # Safety
+    ///
+    /// - `memory` must have been allocated from this device and be mapped.
+    /// - The range must lie within the mapped region.
+    /// - `allocation_size` must be the size of `memory`.
+    /// - `non_coherent_atom_size` must be the limit of this device.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(target = "gpu_alloc::vulkanalia", skip(self))
+    )]
+    pub unsafe fn sync_to_device(
+        &self,
+        memory: vk::DeviceMemory,
+        offset: u64,
+        size: u64,
+        allocation_size: u64,
+        non_coherent_atom_size: u64,
+    ) -> Result<(), VulkanaliaMemoryError> {
+        let range = MappedMemoryRange {
+            memory: &memory,
+            offset,
+            size,
+        };
+        self.flush_memory_ranges_aligned(&[(range, allocation_size)], non_coherent_atom_size)
+    }
+
+    /// Makes device writes to a mapped region of `memory` visible to the host,
+    /// e.g. before reading back results of a compute dispatch.
+    ///
+    /// Invalidates a single range aligned with [`align_range_to_atom`].
+    /// For host coherent memory this is not required, but still valid.
+    ///
+    /// # Safety
+    ///
+    /// - `memory` must have been allocated from this device and be mapped.
+    /// - The range must lie within the mapped region.
+    /// - `allocation_size` must be the size of `memory`.
+    /// - `non_coherent_atom_size` must be the limit of this device.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(target = "gpu_alloc::vulkanalia", skip(self))
+    )]
+    pub unsafe fn sync_to_host(
+        &self,
+        memory: vk::DeviceMemory,
+        offset: u64,
+        size: u64,
+        allocation_size: u64,
+        non_coherent_atom_size: u64,
+    ) -> Result<(), VulkanaliaMemoryError> {
+        let range = MappedMemoryRange {
+            memory: &memory,
+            offset,
+            size,
+        };
+        self.invalidate_memory_ranges_aligned(&[(range, allocation_size)], non_coherent_atom_size)
+    }
+
+    /// Flushes ranges of memory mapped regions,
+    /// aligning each of them with [`align_range_to_atom`] first.
+    ///
+    /// Each range is paired with the size of its memory object.
+    /// Ranges which become empty after alignment are skipped.
+    ///
+    /// # Safety
+    ///
+    /// Same as for [`MemoryDevice::flush_memory_ranges`](gpu_alloc_types::MemoryDevice::flush_memory_ranges),
+    /// except for the alignment requirement.
+    /// `non_coherent_atom_size` must be the limit of this device.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            target = "gpu_alloc::vulkanalia",
+            skip(self, ranges),
+            fields(ranges = ranges.len())
+        )
+    )]
+    pub unsafe fn flush_memory_ranges_aligned(
+        &self,
+        ranges: &[(MappedMemoryRange<'_, vk::DeviceMemory>, u64)],
+        non_coherent_atom_size: u64,
+    ) -> Result<(), VulkanaliaMemoryError> {
+        let ranges = aligned_ranges(
+            ranges.iter().map(|(range, allocation_size)| {
+                (*range.memory, range.offset, range.size, *allocation_size)
+            }),
+            non_coherent_atom_size,
+        );
+        if ranges.is_empty() {
+            return Ok(());
+        }
+
+        self.device
+            .flush_mapped_memory_ranges(&ranges)
+            .map_err(VulkanaliaMemoryError::from)
+    }
+
+    /// Invalidates ranges of memory mapped regions,
+    /// aligning each of them with [`align_range_to_atom`] first.
+    ///
+    /// Each range is paired with the size of its memory object.
+    /// Ranges which become empty after alignment are skipped.
+    ///
+    /// # Safety
+    ///
+    /// Same as for [`MemoryDevice::invalidate_memory_ranges`](gpu_alloc_types::MemoryDevice::invalidate_memory_ranges),
+    /// except for the alignment requirement.
+    /// `non_coherent_atom_size` must be the limit of this device.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            target = "gpu_alloc::vulkanalia",
+            skip(self, ranges),
+            fields(ranges = ranges.len())
+        )
+    )]
+    pub unsafe fn invalidate_memory_ranges_aligned(
+        &self,
+        ranges: &[(MappedMemoryRange<'_, vk::DeviceMemory>, u64)],
+        non_coherent_atom_size: u64,
+    ) -> Result<(), VulkanaliaMemoryError> {
+        let ranges = aligned_ranges(
+            ranges.iter().map(|(range, allocation_size)| {
+                (*range.memory, range.offset, range.size, *allocation_size)
+            }),
+            non_coherent_atom_size,
+        );
+        if ranges.is_empty() {
+            return Ok(());
+        }
+
+        self.device
+            .invalidate_mapped_memory_ranges(&ranges)
+            .map_err(VulkanaliaMemoryError::from)
+    }
+
+    /// Flushes ranges of possibly different memory objects with a single
+    /// `vkFlushMappedMemoryRanges` call.
+    ///
+    /// Ranges of host coherent memory types are skipped, the rest are aligned
+    /// with [`align_range_to_atom`] using `non_coherent_atom_size` of `props`.
+    /// Nothing is called when no range is left.
+    ///
+    /// # Safety
+    ///
+    /// - Every memory object must have been allocated from this device
+    ///   with the range's memory type and be mapped.
+    /// - Every range must lie within the mapped region.
+    /// - `props` must be collected for the physical device of this device.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            target = "gpu_alloc::vulkanalia",
+            skip(self, props, ranges),
+            fields(ranges = ranges.len())
+        )
+    )]
+    pub unsafe fn flush_ranges_aligned(
+        &self,
+        props: &DeviceProperties<'_>,
+        ranges: &[TypedMemoryRange],
+    ) -> Result<(), VulkanaliaMemoryError> {
+        let ranges = non_coherent_ranges(props, ranges);
+        if ranges.is_empty() {
+            return Ok(());
+        }
+
+        self.device
+            .flush_mapped_memory_ranges(&ranges)
+            .map_err(VulkanaliaMemoryError::from)
+    }
+}
+
+/// Aligns ranges of non host coherent memory types for [`VulkanaliaMemoryDevice::flush_ranges_aligned`],
+/// skipping the coherent ones.
+fn non_coherent_ranges(
+    props: &DeviceProperties<'_>,
+    ranges: &[TypedMemoryRange],
+) -> RangeVec<DEFAULT_RANGES_INLINE_CAPACITY> {
+    aligned_ranges(
+        ranges
+            .iter()
+            .filter(|range| !is_host_coherent(props, range.memory_type))
+            .map(|range| {
+                (
+                    range.memory,
+                    range.offset,
+                    range.size,
+                    range.allocation_size,
+                )
+            }),
+        non_coherent_atom_size(props),
+    )
+}
+
+/// Aligns `(memory, offset, size, allocation_size)` ranges with [`align_range_to_atom`],
+/// skipping ranges which become empty.
+fn aligned_ranges(
+    ranges: impl Iterator<Item = (vk::DeviceMemory, u64, u64, u64)>,
+    atom_size: u64,
+) -> RangeVec<DEFAULT_RANGES_INLINE_CAPACITY> {
+    ranges
+        .filter_map(|(memory, offset, size, allocation_size)| {
+            let (offset, size) = align_range_to_atom(offset, size, atom_size, allocation_size);
+            (size != 0).then(|| {
+                vk::MappedMemoryRange::builder()
+                    .memory(memory)
+                    .offset(offset)
+                    .size(size)
+                    .build()
+            })
+        })
+        .collect()
+}
+
+#[cfg(feature = "smallvec")]
+type RangeVec<const N: usize> = SmallVec<[vk::MappedMemoryRange; N]>;
+
+/// Ranges stored in a stack array while they fit into `N` elements
+/// and in a `Vec` otherwise, a minimal replacement for `SmallVec`.
+#[cfg(not(feature = "smallvec"))]
+enum RangeVec<const N: usize> {
+    Inline([vk::MappedMemoryRange; N], usize),
+    Heap(Vec<vk::MappedMemoryRange>),
+}
+
+#[cfg(not(feature = "smallvec"))]
+impl<const N: usize> FromIterator<vk::MappedMemoryRange> for RangeVec<N> {
+    fn from_iter<I: IntoIterator<Item = vk::MappedMemoryRange>>(iter: I) -> Self {
+        let mut iter = iter.into_iter();
+
+        let mut inline = [vk::MappedMemoryRange::default(); N];
+        let mut len = 0;
+        while len < N {
+            match iter.next() {
+                Some(range) => {
+                    inline[len] = range;
+                    len += 1;
+                }
+                None => return Self::Inline(inline, len),
+            }
+        }
+
+        match iter.next() {
+            None => Self::Inline(inline, len),
+            Some(range) => {
+                let mut heap = Vec::with_capacity(N + 1 + iter.size_hint().0);
+                heap.extend_from_slice(&inline);
+                heap.push(range);
+                heap.extend(iter);
+                Self::Heap(heap)
+            }
+        }
+    }
+}
+
+#[cfg(not(feature = "smallvec"))]
+impl<const N: usize> std::ops::Deref for RangeVec<N> {
+    type Target = [vk::MappedMemoryRange];
+
+    fn deref(&self) -> &Self::Target {
+        match self {
+            Self::Inline(ranges, len) => &ranges[..*len],
+            Self::Heap(ranges) => ranges,
+        }
+    }
+}
+
+#[track_caller]
+fn range_error(e: vk::ErrorCode) -> OutOfMemory {
+    match e {
+        vk::ErrorCode::OUT_OF_DEVICE_MEMORY => OutOfMemory::OutOfDeviceMemory,
+        vk::ErrorCode::OUT_OF_HOST_MEMORY => OutOfMemory::OutOfHostMemory,
+        e => unexpected_error(e, OutOfMemory::OutOfDeviceMemory),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::borrow::Cow;
+
+    use gpu_alloc_types::{MemoryHeap, MemoryPropertyFlags, MemoryType};
+
+    use super::*;
+
+    #[test]
+    fn align_range_to_atom_expands_to_atoms() {
+        assert_eq!(align_range_to_atom(64, 64, 64, 256), (64, 64));
+        assert_eq!(align_range_to_atom(70, 10, 64, 256), (64, 64));
+        assert_eq!(align_range_to_atom(60, 10, 64, 256), (0, 128));
+        assert_eq!(align_range_to_atom(70, 10, 0, 256), (70, 10));
+    }
+
+    #[test]
+    fn align_range_to_atom_clamps_to_whole_size() {
+        assert_eq!(align_range_to_atom(200, 50, 64, 250), (192, vk::WHOLE_SIZE));
+        assert_eq!(align_range_to_atom(192, 64, 64, 256), (192, 64));
+    }
+
+    #[test]
+    fn align_range_to_atom_keeps_special_sizes() {
+        assert_eq!(align_range_to_atom(70, 0, 64, 256), (64, 0));
+        assert_eq!(
+            align_range_to_atom(70, vk::WHOLE_SIZE, 64, 256),
+            (64, vk::WHOLE_SIZE)
+        );
+    }
+
+    #[test]
+    fn to_vk_mapped_range_copies_fields() {
+        let memory = vk::DeviceMemory::from_raw(7);
+        let range = MappedMemoryRange {
+            memory: &memory,
+            offset: 100,
+            size: 30,
+        };
+
+        let vk_range = to_vk_mapped_range(&range);
+
+        assert_eq!(vk_range.s_type, vk::StructureType::MAPPED_MEMORY_RANGE);
+        assert!(vk_range.next.is_null());
+        assert_eq!(vk_range.memory, memory);
+        assert_eq!(vk_range.offset, 100);
+        assert_eq!(vk_range.size, 30);
+    }
+
+    #[test]
+    fn whole_size_is_passed_to_vulkan_as_is() {
+        let memory = vk::DeviceMemory::from_raw(1);
+        let range = || MappedMemoryRange {
+            memory: &memory,
+            offset: 70,
+            size: vk::WHOLE_SIZE,
+        };
+
+        // More ranges than fit inline, like `flush_memory_ranges_n` would convert them
+        let ranges = [range(), range()]
+            .iter()
+            .map(to_vk_mapped_range)
+            .collect::<RangeVec<1>>();
+
+        assert_eq!(ranges.len(), 2);
+        for range in ranges.iter() {
+            assert_eq!((range.offset, range.size), (70, vk::WHOLE_SIZE));
+        }
+    }
+
+    #[test]
+    fn align_range_to_atom_empties_ranges_past_the_end() {
+        assert_eq!(align_range_to_atom(256, 64, 64, 256), (256, 0));
+        assert_eq!(align_range_to_atom(300, 10, 64, 256), (256, 0));
+        assert_eq!(align_range_to_atom(256, vk::WHOLE_SIZE, 64, 256), (256, 0));
+        assert_eq!(align_range_to_atom(0, vk::WHOLE_SIZE, 64, 0), (0, 0));
+        assert_eq!(
+            align_range_to_atom(255, vk::WHOLE_SIZE, 64, 256),
+            (192, vk::WHOLE_SIZE)
+        );
+    }
+
+    #[test]
+    fn align_range_to_atom_handles_overflow() {
+        let offset = u64::MAX - 10;
+        let aligned_offset = offset - offset % 64;
+        assert_eq!(
+            align_range_to_atom(offset, 5, 64, u64::MAX),
+            (aligned_offset, vk::WHOLE_SIZE)
+        );
+        assert_eq!(
+            align_range_to_atom(10, u64::MAX - 5, 64, u64::MAX),
+            (0, vk::WHOLE_SIZE)
+        );
+    }
+
+    #[test]
+    fn pad_to_non_coherent_atom_rounds_up() {
+        assert_eq!(pad_to_non_coherent_atom(0, 64), 0);
+        assert_eq!(pad_to_non_coherent_atom(1, 64), 64);
+        assert_eq!(pad_to_non_coherent_atom(64, 64), 64);
+        assert_eq!(pad_to_non_coherent_atom(65, 64), 128);
+        assert_eq!(pad_to_non_coherent_atom(5, 0), 5);
+    }
+
+    #[test]
+    fn pad_to_non_coherent_atom_keeps_overflowing_sizes() {
+        assert_eq!(pad_to_non_coherent_atom(u64::MAX, 64), u64::MAX);
+        assert_eq!(pad_to_non_coherent_atom(u64::MAX - 1, 64), u64::MAX - 1);
+        assert_eq!(pad_to_non_coherent_atom(u64::MAX, 1), u64::MAX);
+    }
+
+    #[test]
+    fn non_coherent_ranges_skip_coherent_memory_types() {
+        let memory_type = |props| MemoryType { props, heap: 0 };
+        let props = DeviceProperties {
+            memory_types: Cow::Owned(vec![
+                memory_type(MemoryPropertyFlags::HOST_VISIBLE),
+                memory_type(MemoryPropertyFlags::HOST_VISIBLE | MemoryPropertyFlags::HOST_COHERENT),
+            ]),
+            memory_heaps: Cow::Owned(vec![MemoryHeap { size: 1 << 30 }]),
+            max_memory_allocation_count: 4096,
+            max_memory_allocation_size: u64::MAX,
+            non_coherent_atom_size: 64,
+            buffer_device_address: false,
+        };
+        let range = |memory_type, offset, size| TypedMemoryRange {
+            memory: vk::DeviceMemory::null(),
+            memory_type,
+            offset,
+            size,
+            allocation_size: 1024,
+        };
+
+        let ranges = non_coherent_ranges(
+            &props,
+            &[
+                range(0, 70, 10),
+                range(1, 0, 64),
+                range(0, 128, 0),
+                range(1, 256, 10),
+                range(0, 200, 100),
+                // Past the end of the memory object
+                range(0, 1024, vk::WHOLE_SIZE),
+            ],
+        );
+
+        let ranges = ranges
+            .iter()
+            .map(|range| (range.offset, range.size))
+            .collect::<Vec<_>>();
+        assert_eq!(ranges, [(64, 64), (192, 128)]);
+    }
+}
@@ -0,0 +1,246 @@
+use gpu_alloc_types::{DeviceProperties, MemoryPropertyFlags};
+use vulkanalia::prelude::v1_0::*;
+use vulkanalia::vk::{DeviceV1_1, KhrGetMemoryRequirements2ExtensionDeviceCommands};
+
+use crate::find_memory_type;
+
+/// Returns memory requirements of the buffer.
+///
+/// # Safety
+///
+/// `buffer` must be created from the `device`.
+pub unsafe fn buffer_memory_requirements(
+    device: &Device,
+    buffer: vk::Buffer,
+) -> vk::MemoryRequirements {
+    device.get_buffer_memory_requirements(buffer)
+}
+
+/// Returns memory requirements of the image.
+///
+/// # Safety
+///
+/// `image` must be created from the `device`.
+pub unsafe fn image_memory_requirements(
+    device: &Device,
+    image: vk::Image,
+) -> vk::MemoryRequirements {
+    device.get_image_memory_requirements(image)
+}
+
+/// Returns memory requirements of the buffer along with index of the first memory type
+/// allowed by them and having `required_flags`, see [`find_memory_type`].
+///
+/// Returns `None` when no memory type matches.
+///
+/// # Safety
+///
+/// - `buffer` must be created from the `device`.
+/// - `props` must be collected for the physical device of the `device`.
+pub unsafe fn buffer_memory_type(
+    device: &Device,
+    props: &DeviceProperties<'_>,
+    buffer: vk::Buffer,
+    required_flags: MemoryPropertyFlags,
+) -> Option<(vk::MemoryRequirements, u32)> {
+    let requirements = buffer_memory_requirements(device, buffer);
+    memory_type_for(props, requirements, required_flags)
+}
+
+/// Returns memory requirements of the image along with index of the first memory type
+/// allowed by them and having `required_flags`, see [`find_memory_type`].
+///
+/// Returns `None` when no memory type matches.
+///
+/// # Safety
+///
+/// - `image` must be created from the `device`.
+/// - `props` must be collected for the physical device of the `device`.
+pub unsafe fn image_memory_type(
+    device: &Device,
+    props: &DeviceProperties<'_>,
+    image: vk::Image,
+    required_flags: MemoryPropertyFlags,
+) -> Option<(vk::MemoryRequirements, u32)> {
+    let requirements = image_memory_requirements(device, image);
+    memory_type_for(props, requirements, required_flags)
+}
+
+/// Whether a resource should be bound to a dedicated allocation,
+/// as reported by `VkMemoryDedicatedRequirements`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct DedicatedRequirements {
+    /// A dedicated allocation is preferred for better performance.
+    pub prefers_dedicated: bool,
+    /// A dedicated allocation is required.
+    pub requires_dedicated: bool,
+}
+
+/// Returns memory requirements of the buffer along with its dedicated allocation requirements.
+///
+/// Uses `vkGetBufferMemoryRequirements2` for Vulkan 1.1 and above
+/// and `vkGetBufferMemoryRequirements2KHR` otherwise.
+///
+/// # Safety
+///
+/// - `version` must not be higher than the `api_version` of the `device`'s instance.
+/// - For Vulkan 1.0 extensions "VK_KHR_get_memory_requirements2" and
+///   "VK_KHR_dedicated_allocation" must be enabled.
+/// - `buffer` must be created from the `device`.
+pub unsafe fn buffer_dedicated_requirements(
+    device: &Device,
+    version: u32,
+    buffer: vk::Buffer,
+) -> (vk::MemoryRequirements, DedicatedRequirements) {
+    let info = vk::BufferMemoryRequirementsInfo2::builder().buffer(buffer);
+
+    let mut requirements = vk::MemoryRequirements2::builder();
+    let mut dedicated = vk::MemoryDedicatedRequirements::builder();
+    requirements = requirements.push_next(&mut dedicated);
+
+    if vk::version_minor(version) >= 1 {
+        device.get_buffer_memory_requirements2(&info, &mut requirements);
+    } else {
+        device.get_buffer_memory_requirements2_khr(&info, &mut requirements);
+    }
+
+    (
+        requirements.memory_requirements,
+        dedicated_requirements_from(&dedicated),
+    )
+}
+
+/// Returns memory requirements of the image along with its dedicated allocation requirements.
+///
+/// Uses `vkGetImageMemoryRequirements2` for Vulkan 1.1 and above
+/// and `vkGetImageMemoryRequirements2KHR` otherwise.
+///
+/// # Safety
+///
+/// - `version` must not be higher than the `api_version` of the `device`'s instance.
+/// - For Vulkan 1.0 extensions "VK_KHR_get_memory_requirements2" and
+///   "VK_KHR_dedicated_allocation" must be enabled.
+/// - `image` must be created from the `device` and must not be disjoint.
+pub unsafe fn image_dedicated_requirements(
+    device: &Device,
+    version: u32,
+    image: vk::Image,
+) -> (vk::MemoryRequirements, DedicatedRequirements) {
+    let info = vk::ImageMemoryRequirementsInfo2::builder().image(image);
+
+    let mut requirements = vk::MemoryRequirements2::builder();
+    let mut dedicated = vk::MemoryDedicatedRequirements::builder();
+    requirements = requirements.push_next(&mut dedicated);
+
+    if vk::version_minor(version) >= 1 {
+        device.get_image_memory_requirements2(&info, &mut requirements);
+    } else {
+        device.get_image_memory_requirements2_khr(&info, &mut requirements);
+    }
+
+    (
+        requirements.memory_requirements,
+        dedicated_requirements_from(&dedicated),
+    )
+}
+
+fn dedicated_requirements_from(
+    dedicated: &vk::MemoryDedicatedRequirements,
+) -> DedicatedRequirements {
+    DedicatedRequirements {
+        prefers_dedicated: dedicated.prefers_dedicated_allocation != 0,
+        requires_dedicated: dedicated.requires_dedicated_allocation != 0,
+    }
+}
+
+fn memory_type_for(
+    props: &DeviceProperties<'_>,
+    requirements: vk::MemoryRequirements,
+    required_flags: MemoryPropertyFlags,
+) -> Option<(vk::MemoryRequirements, u32)> {
+    let memory_type = find_memory_type(
+        props,
+        requirements.memory_type_bits,
+        required_flags,
+        MemoryPropertyFlags::empty(),
+    )?;
+    Some((requirements, memory_type))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::borrow::Cow;
+
+    use gpu_alloc_types::{MemoryHeap, MemoryType};
+
+    use super::*;
+
+    #[test]
+    fn dedicated_requirements_from_reads_both_flags() {
+        for (prefers, requires) in [(false, false), (true, false), (false, true), (true, true)] {
+            let dedicated = vk::MemoryDedicatedRequirements {
+                prefers_dedicated_allocation: prefers as vk::Bool32,
+                requires_dedicated_allocation: requires as vk::Bool32,
+                ..Default::default()
+            };
+            assert_eq!(
+                dedicated_requirements_from(&dedicated),
+                DedicatedRequirements {
+                    prefers_dedicated: prefers,
+                    requires_dedicated: requires,
+                }
+            );
+        }
+    }
+
+    #[test]
+    fn memory_type_for_respects_type_bits_and_flags() {
+        let memory_type = |props| MemoryType { props, heap: 0 };
+        let props = DeviceProperties {
+            memory_types: Cow::Owned(vec![
+                memory_type(MemoryPropertyFlags::DEVICE_LOCAL),
+                memory_type(MemoryPropertyFlags::HOST_VISIBLE),
+                memory_type(MemoryPropertyFlags::HOST_VISIBLE | MemoryPropertyFlags::HOST_COHERENT),
+            ]),
+            memory_heaps: Cow::Owned(vec![MemoryHeap { size: 1 << 30 }]),
+            max_memory_allocation_count: 4096,
+            max_memory_allocation_size: u64::MAX,
+            non_coherent_atom_size: 64,
+            buffer_device_address: false,
+        };
+        let requirements = |memory_type_bits| vk::MemoryRequirements {
+            size: 256,
+            alignment: 64,
+            memory_type_bits,
+        };
+
+        assert_eq!(
+            memory_type_for(
+                &props,
+                requirements(0b111),
+                MemoryPropertyFlags::HOST_VISIBLE
+            ),
+            Some((requirements(0b111), 1))
+        );
+        assert_eq!(
+            memory_type_for(
+                &props,
+                requirements(0b101),
+                MemoryPropertyFlags::HOST_VISIBLE
+            ),
+            Some((requirements(0b101), 2))
+        );
+        assert_eq!(
+            memory_type_for(
+                &props,
+                requirements(0b001),
+                MemoryPropertyFlags::HOST_VISIBLE
+            ),
+            None
+        );
+        assert_eq!(
+            memory_type_for(&props, requirements(0b111), MemoryPropertyFlags::PROTECTED),
+            None
+        );
+    }
+}
@@ -0,0 +1,346 @@
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::hash::Hash;
+use std::ptr::NonNull;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Mutex, MutexGuard};
+
+use gpu_alloc_types::{
+    AllocationFlags, DeviceMapError, MappedMemoryRange, MemoryDevice, OutOfMemory,
+};
+use vulkanalia::prelude::v1_0::*;
+
+use crate::{lock_ignoring_poison, VulkanaliaMemoryDevice};
+
+/// A wrapper around a memory device which keeps track of live memory objects,
+/// e.g. to check that nothing leaked at shutdown.
+///
+/// Every allocated memory object is recorded along with its size
+/// and forgotten when deallocated through this wrapper.
+/// Cheaper running totals are available through [`stats`](Self::stats).
+///
+/// Any memory device can be wrapped, e.g. `MockMemoryDevice`
+/// with its `u64` memory handles as `M`.
+///
+/// With `tracing` or `log` feature deallocations are reported along with the freed size,
+/// which [`VulkanaliaMemoryDevice`] alone cannot know, and a warning is emitted
+/// on drop if any memory object is still live.
+pub struct TrackingMemoryDevice<'a, D: ?Sized = VulkanaliaMemoryDevice, M = vk::DeviceMemory> {
+    device: &'a D,
+    allocations: Mutex<HashMap<M, u64>>,
+    stats: Stats,
+    max_allocation_count: Option<usize>,
+}
+
+/// Allocation counters of a [`TrackingMemoryDevice`].
+///
+/// Each counter is updated atomically, but counters are not updated together,
+/// so a [`snapshot`](Self::snapshot) taken during an allocation may be
+/// slightly inconsistent, e.g. count a new allocation but not its bytes yet.
+#[derive(Debug, Default)]
+pub struct Stats {
+    live_allocations: AtomicU64,
+    live_bytes: AtomicU64,
+    peak_bytes: AtomicU64,
+    allocation_calls: AtomicU64,
+}
+
+/// Values of [`Stats`] counters at some point in time.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct StatsSnapshot {
+    /// Number of memory objects allocated and not yet deallocated.
+    pub live_allocations: u64,
+    /// Total size in bytes of memory objects allocated and not yet deallocated.
+    pub live_bytes: u64,
+    /// Maximum observed value of `live_bytes`.
+    pub peak_bytes: u64,
+    /// Number of `allocate_memory` calls, including failed ones.
+    pub allocation_calls: u64,
+}
+
+impl Stats {
+    /// Reads current values of the counters without locking.
+    pub fn snapshot(&self) -> StatsSnapshot {
+        StatsSnapshot {
+            live_allocations: self.live_allocations.load(Ordering::Relaxed),
+            live_bytes: self.live_bytes.load(Ordering::Relaxed),
+            peak_bytes: self.peak_bytes.load(Ordering::Relaxed),
+            allocation_calls: self.allocation_calls.load(Ordering::Relaxed),
+        }
+    }
+
+    fn on_allocated(&self, size: u64) {
+        self.live_allocations.fetch_add(1, Ordering::Relaxed);
+        let live_bytes = self.live_bytes.fetch_add(size, Ordering::Relaxed) + size;
+        self.peak_bytes.fetch_max(live_bytes, Ordering::Relaxed);
+    }
+
+    fn on_deallocated(&self, size: u64) {
+        self.live_allocations.fetch_sub(1, Ordering::Relaxed);
+        self.live_bytes.fetch_sub(size, Ordering::Relaxed);
+    }
+}
+
+impl<'a, D, M> TrackingMemoryDevice<'a, D, M>
+where
+    D: MemoryDevice<M> + ?Sized,
+    M: Hash + Eq + Copy + Debug,
+{
+    /// Wraps the memory device, starting with no tracked memory objects.
+    pub fn new(device: &'a D) -> Self {
+        Self {
+            device,
+            allocations: Mutex::new(HashMap::new()),
+            stats: Stats::default(),
+            max_allocation_count: None,
+        }
+    }
+
+    /// Rejects allocations with [`OutOfMemory::OutOfDeviceMemory`] while `count`
+    /// memory objects are live, before calling the wrapped device.
+    ///
+    /// Use `max_memory_allocation_count` of the device properties to turn exceeding
+    /// the Vulkan limit into an early error instead of a driver failure.
+    /// `gpu-alloc` suballocates from a few large memory objects, so it normally stays
+    /// far below the limit. Concurrent allocations are checked independently,
+    /// so they may exceed the limit by the number of threads allocating at once.
+    pub fn with_max_allocation_count(mut self, count: u32) -> Self {
+        self.max_allocation_count = Some(count as usize);
+        self
+    }
+
+    /// Returns the wrapped memory device.
+    pub fn inner(&self) -> &'a D {
+        self.device
+    }
+
+    /// Returns the number of memory objects allocated and not yet deallocated.
+    pub fn outstanding_count(&self) -> usize {
+        self.lock().len()
+    }
+
+    /// Returns the total size in bytes of memory objects allocated and not yet deallocated.
+    pub fn outstanding_total_bytes(&self) -> u64 {
+        self.lock().values().sum()
+    }
+
+    /// Returns allocation counters, cheap enough to be read every frame.
+    pub fn stats(&self) -> &Stats {
+        &self.stats
+    }
+
+    /// Stops tracking the memory object, returning its size reported as freed
+    /// or `None` if it was not tracked.
+    fn untrack(&self, memory: M) -> Option<u64> {
+        let size = self.lock().remove(&memory)?;
+        self.stats.on_deallocated(size);
+
+        #[cfg(feature = "tracing")]
+        tracing::trace!(
+            target: "gpu_alloc::vulkanalia",
+            ?memory,
+            freed_bytes = size,
+            "Memory deallocated"
+        );
+        #[cfg(feature = "log")]
+        log::trace!(
+            target: "gpu_alloc::vulkanalia",
+            "Memory {memory:?} deallocated, freed {size} bytes"
+        );
+        Some(size)
+    }
+
+    fn lock(&self) -> MutexGuard<'_, HashMap<M, u64>> {
+        lock_ignoring_poison(&self.allocations)
+    }
+}
+
+impl<D, M> MemoryDevice<M> for TrackingMemoryDevice<'_, D, M>
+where
+    D: MemoryDevice<M> + ?Sized,
+    M: Hash + Eq + Copy + Debug,
+{
+    unsafe fn allocate_memory(
+        &self,
+        size: u64,
+        memory_type: u32,
+        flags: AllocationFlags,
+    ) -> Result<M, OutOfMemory> {
+        self.stats.allocation_calls.fetch_add(1, Ordering::Relaxed);
+
+        if let Some(max_allocation_count) = self.max_allocation_count {
+            let count = self.lock().len();
+            if count >= max_allocation_count {
+                #[cfg(feature = "tracing")]
+                tracing::warn!(
+                    target: "gpu_alloc::vulkanalia",
+                    count,
+                    size,
+                    memory_type,
+                    "Memory allocation count limit reached"
+                );
+                #[cfg(feature = "log")]
+                log::warn!(
+                    target: "gpu_alloc::vulkanalia",
+                    "Memory allocation count limit reached with {count} live memory objects, \
+                     rejecting {size} bytes from memory type {memory_type}"
+                );
+                return Err(OutOfMemory::OutOfDeviceMemory);
+            }
+        }
+
+        let memory = self.device.allocate_memory(size, memory_type, flags)?;
+        self.lock().insert(memory, size);
+        self.stats.on_allocated(size);
+        Ok(memory)
+    }
+
+    unsafe fn deallocate_memory(&self, memory: M) {
+        self.untrack(memory);
+        self.device.deallocate_memory(memory);
+    }
+
+    unsafe fn map_memory(
+        &self,
+        memory: &mut M,
+        offset: u64,
+        size: u64,
+    ) -> Result<NonNull<u8>, DeviceMapError> {
+        self.device.map_memory(memory, offset, size)
+    }
+
+    unsafe fn unmap_memory(&self, memory: &mut M) {
+        self.device.unmap_memory(memory);
+    }
+
+    unsafe fn invalidate_memory_ranges(
+        &self,
+        ranges: &[MappedMemoryRange<'_, M>],
+    ) -> Result<(), OutOfMemory> {
+        self.device.invalidate_memory_ranges(ranges)
+    }
+
+    unsafe fn flush_memory_ranges(
+        &self,
+        ranges: &[MappedMemoryRange<'_, M>],
+    ) -> Result<(), OutOfMemory> {
+        self.device.flush_memory_ranges(ranges)
+    }
+}
+
+impl<D: ?Sized, M> Drop for TrackingMemoryDevice<'_, D, M> {
+    fn drop(&mut self) {
+        #[cfg(any(feature = "tracing", feature = "log"))]
+        {
+            let allocations = self
+                .allocations
+                .get_mut()
+                .unwrap_or_else(|e| e.into_inner());
+            if !allocations.is_empty() {
+                let count = allocations.len();
+                let bytes = allocations.values().sum::<u64>();
+                #[cfg(feature = "tracing")]
+                tracing::warn!(
+                    target: "gpu_alloc::vulkanalia",
+                    count,
+                    bytes,
+                    "Memory objects leaked"
+                );
+                #[cfg(feature = "log")]
+                log::warn!(
+                    target: "gpu_alloc::vulkanalia",
+                    "{count} memory objects leaked, {bytes} bytes in total"
+                );
+            }
+        }
+    }
+}
+
+#[cfg(all(test, feature = "mock"))]
+mod tests {
+    use super::*;
+    use crate::MockMemoryDevice;
+
+    #[test]
+    fn tracks_mock_memory_objects() {
+        let mock = MockMemoryDevice::new();
+        let device = TrackingMemoryDevice::new(&mock);
+
+        unsafe {
+            let first = device
+                .allocate_memory(64, 0, AllocationFlags::empty())
+                .unwrap();
+            let second = device
+                .allocate_memory(256, 0, AllocationFlags::empty())
+                .unwrap();
+            assert_eq!(device.outstanding_count(), 2);
+            assert_eq!(device.outstanding_total_bytes(), 320);
+
+            device.deallocate_memory(first);
+            assert_eq!(device.outstanding_count(), 1);
+            assert_eq!(device.outstanding_total_bytes(), 256);
+
+            device.deallocate_memory(second);
+        }
+
+        let stats = device.stats().snapshot();
+        assert_eq!(
+            stats,
+            StatsSnapshot {
+                live_allocations: 0,
+                live_bytes: 0,
+                peak_bytes: 320,
+                allocation_calls: 2,
+            }
+        );
+        assert_eq!(mock.live_count(), 0);
+    }
+
+    #[test]
+    fn rejects_allocations_over_max_count() {
+        let mock = MockMemoryDevice::new();
+        let device = TrackingMemoryDevice::new(&mock).with_max_allocation_count(1);
+
+        unsafe {
+            let memory = device
+                .allocate_memory(64, 0, AllocationFlags::empty())
+                .unwrap();
+            assert!(matches!(
+                device.allocate_memory(64, 0, AllocationFlags::empty()),
+                Err(OutOfMemory::OutOfDeviceMemory)
+            ));
+            assert_eq!(mock.calls().allocate, 1);
+            device.deallocate_memory(memory);
+        }
+
+        assert_eq!(device.stats().snapshot().allocation_calls, 2);
+    }
+
+    #[test]
+    fn reports_freed_size_of_each_memory_object() {
+        let mock = MockMemoryDevice::new();
+        let device = TrackingMemoryDevice::new(&mock);
+
+        unsafe {
+            let first = device
+                .allocate_memory(64, 0, AllocationFlags::empty())
+                .unwrap();
+            let second = device
+                .allocate_memory(256, 0, AllocationFlags::empty())
+                .unwrap();
+
+            assert_eq!(device.untrack(second), Some(256));
+            assert_eq!(device.untrack(second), None);
+            assert_eq!(device.stats().snapshot().live_bytes, 64);
+            mock.deallocate_memory(second);
+
+            let live_bytes = device.stats().snapshot().live_bytes;
+            device.deallocate_memory(first);
+            let freed = live_bytes - device.stats().snapshot().live_bytes;
+            assert_eq!(freed, 64);
+        }
+
+        assert_eq!(device.outstanding_count(), 0);
+        assert_eq!(mock.live_count(), 0);
+    }
+}
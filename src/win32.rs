@@ -0,0 +1,94 @@
+use gpu_alloc_types::AllocationFlags;
+use vulkanalia::prelude::v1_0::*;
+use vulkanalia::vk::KhrExternalMemoryWin32ExtensionDeviceCommands;
+
+use crate::{AllocateExtensions, VulkanaliaMemoryDevice, VulkanaliaMemoryError};
+
+/// Windows `HANDLE` exported from a memory object.
+///
+/// The handle is owned by the caller: it is not closed on drop and must be
+/// released with `CloseHandle` once it is no longer needed,
+/// unless it was imported back into Vulkan, which takes over ownership.
+/// Handles of type `OPAQUE_WIN32_KMT` are not reference counted and must not be closed.
+#[derive(Debug, PartialEq, Eq, Hash)]
+#[repr(transparent)]
+pub struct Win32Handle(vk::HANDLE);
+
+impl Win32Handle {
+    /// Wraps a raw handle, taking ownership of it.
+    pub fn from_raw(handle: vk::HANDLE) -> Self {
+        Self(handle)
+    }
+
+    /// Returns the raw handle without giving up ownership.
+    pub fn as_raw(&self) -> vk::HANDLE {
+        self.0
+    }
+
+    /// Returns the raw handle, transferring its ownership to the caller.
+    pub fn into_raw(self) -> vk::HANDLE {
+        self.0
+    }
+}
+
+impl VulkanaliaMemoryDevice {
+    /// Allocates new memory object which can be exported as a Windows handle.
+    ///
+    /// Chains `VkExportMemoryAllocateInfo` with the specified `handle_types`
+    /// and, when `win32_info` is `Some`, `VkExportMemoryWin32HandleInfoKHR`
+    /// to set security attributes, access flags and name of the exported handle.
+    /// The `next` pointer of `win32_info` is ignored.
+    ///
+    /// # Safety
+    ///
+    /// Same as for [`allocate_exportable_memory`](Self::allocate_exportable_memory).
+    /// In addition:
+    /// - Extension "VK_KHR_external_memory_win32" must be enabled.
+    /// - Pointers in `win32_info` must be either null or valid for the duration of the call.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(target = "gpu_alloc::vulkanalia", skip(self))
+    )]
+    pub unsafe fn allocate_exportable_memory_win32(
+        &self,
+        size: u64,
+        memory_type: u32,
+        flags: AllocationFlags,
+        handle_types: vk::ExternalMemoryHandleTypeFlags,
+        win32_info: Option<vk::ExportMemoryWin32HandleInfoKHR>,
+    ) -> Result<vk::DeviceMemory, VulkanaliaMemoryError> {
+        let ext = AllocateExtensions {
+            export: Some(handle_types),
+            export_win32: win32_info,
+            ..Default::default()
+        };
+        self.allocate_memory_with(size, memory_type, flags, &ext)
+    }
+
+    /// Exports a Windows handle referencing the memory object.
+    ///
+    /// # Safety
+    ///
+    /// - Extension "VK_KHR_external_memory_win32" must be enabled.
+    /// - `memory` must have been allocated from this device with
+    ///   `handle_type` included in its export handle types.
+    /// - `handle_type` must be either `OPAQUE_WIN32` or `OPAQUE_WIN32_KMT`.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(target = "gpu_alloc::vulkanalia", skip(self))
+    )]
+    pub unsafe fn get_memory_win32_handle(
+        &self,
+        memory: vk::DeviceMemory,
+        handle_type: vk::ExternalMemoryHandleTypeFlags,
+    ) -> Result<Win32Handle, VulkanaliaMemoryError> {
+        let info = vk::MemoryGetWin32HandleInfoKHR::builder()
+            .memory(memory)
+            .handle_type(handle_type);
+
+        self.device
+            .get_memory_win32_handle_khr(&info)
+            .map(Win32Handle)
+            .map_err(VulkanaliaMemoryError::from)
+    }
+}